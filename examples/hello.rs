@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use hostess::{client::{ClientMsg, ServerMsg, tungstenite_client::TungsteniteClient}, master::Master, server::*, uuid::*};
+use hostess::{client::{ClientMsg, Compression, ServerMsg, tungstenite_client::TungsteniteClient}, master::Master, server::*, uuid::*};
 use tokio::task::JoinHandle;
 #[derive(Default)]
 struct HelloServer {
@@ -10,8 +10,9 @@ struct HelloServer {
 impl Server for HelloServer {
     fn init(&mut self) -> Config {
         Config {
-            tick_rate: 5,
+            tick_period: tick_rate(5.0),
             max_players: 8,
+            ..Default::default()
         }
     }
 
@@ -22,6 +23,7 @@ impl Server for HelloServer {
                 InMsg::ClientLeft { client_id: _ } => self.players -= 1,
                 InMsg::CustomMsg { client_id: _, msg:_ } => {
                 },
+                _ => {}
             }
         }
         println!("players:{}", self.players);
@@ -40,6 +42,9 @@ pub fn spawn_client() -> JoinHandle<()> {
         let _ = client.send(ClientMsg::Hello {
             client_id: Uuid::new_v4(),
             client_name: "Test Client".into(),
+            token: None,
+            resume_token: None,
+            compression: vec![Compression::Lz4],
         }).await;
 
         loop {
@@ -50,12 +55,14 @@ pub fn spawn_client() -> JoinHandle<()> {
                         // join host
                         client.send(ClientMsg::JoinInstance {
                             instance_id: hosts.first().unwrap().id,
+                            payload: Vec::new()
                         }).await;
                     },
                     ServerMsg::JoinRejected {
-                        instance: host
+                        instance: host,
+                        reason
                     } => {
-                        println!("failed to join host {:?}", host);
+                        println!("failed to join host {:?}: {}", host, reason);
                     }
                     _ => {}
                 }
@@ -69,7 +76,11 @@ pub fn spawn_client() -> JoinHandle<()> {
 pub fn spawn_master() -> JoinHandle<()> {
     tokio::spawn(async {
         let mut master = Master::new(ADDR, Constructor::new::<HelloServer>());
-        master.new_instance(Uuid::default()).await;
+        if let Err(err) = master.new_instance(Uuid::default()).await {
+            println!("could not create instance: {}", err);
+            return;
+        }
+        master.shutdown_handle().on_signal();
         let _ = master.start().await;
     })
 }
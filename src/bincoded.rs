@@ -1,6 +1,7 @@
 use serde::{Serialize, de::DeserializeOwned};
 
 pub trait Bincoded : Sized + DeserializeOwned + 'static + Serialize {
+    #[allow(clippy::needless_return)]
     fn from_bincode(bytes:&[u8]) -> Option<Self> {
         let res = bincode::deserialize::<Self>(bytes);
         match res {
@@ -9,6 +10,7 @@ pub trait Bincoded : Sized + DeserializeOwned + 'static + Serialize {
         }
     }
 
+    #[allow(clippy::needless_return)]
     fn to_bincode(&self) -> Vec<u8> {
         let res = bincode::serialize::<Self>(self);
         match res {
@@ -72,6 +74,7 @@ pub trait Bincoded : Sized + DeserializeOwned + 'static + Serialize {
         rle
     } 
 
+    #[allow(clippy::needless_return)]
     fn from_delta_bincode(old:&Self, delta:&[u8]) -> Option<Self> {
         let b = old.to_bincode();
 
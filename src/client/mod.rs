@@ -1,20 +1,41 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native_client;
+
 #[cfg(not(target_arch = "wasm32"))]
 pub mod tungstenite_client;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tcp_client;
+
 pub use crate::shared::{InstanceInfo};
 pub use uuid::Uuid;
 pub use serde::{Deserialize, Serialize};
 pub use crate::bincoded::Bincoded;
+pub use crate::compression::{Codec, Compression, Framing};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 /// message sent from Client to Server
 pub enum ClientMsg {
     Hello {
         client_id:Uuid,
-        client_name:String
+        client_name:String,
+
+        /// token used by the master's `Authenticator` to verify the client
+        token:Option<String>,
+
+        /// the `resume_token` of a previous `JoinedLobby`, resuming that session
+        /// and putting the client back into the instance it was in, if its seat is still held
+        resume_token:Option<String>,
+
+        /// compression algorithms supported by the client, in order of preference.
+        /// if not empty, all following messages of the client are framed as described in `compression::Framing`
+        compression:Vec<Compression>
     },
     JoinInstance {
-        instance_id:Uuid
+        instance_id:Uuid,
+
+        /// game specific data passed to `Server::can_join`, e.g. the team the client wants to join
+        payload:Vec<u8>
     },
     LeaveInstance {
     },
@@ -25,6 +46,17 @@ pub enum ClientMsg {
         tick:f64
     },
     RefreshInstances,
+
+    /// asks the master to create a new instance with the client as creator.
+    /// only honored if `master::Config::host_creation` is enabled.
+    /// the client is joined to the instance after creation
+    CreateInstance {
+        /// the kind of game server to host, `None` for the default kind
+        kind:Option<String>,
+
+        /// settings passed to the constructor of the kind, which may reject them
+        settings:Vec<u8>
+    }
 }
 
 
@@ -32,7 +64,21 @@ pub enum ClientMsg {
 /// message sent from Server to Client
 pub enum ServerMsg {
     JoinedLobby {
+        /// the id given to the client by the master, which might differ from the one sent in `Hello`
+        client_id:Uuid,
 
+        /// token to send in `Hello` when reconnecting after a disconnect
+        resume_token:String,
+
+        /// the compression chosen from those offered in `Hello`, `None` if the master does not compress.
+        /// if set, all following messages of the master are framed as described in `compression::Framing`
+        compression:Option<Codec>
+    },
+
+    /// the `Hello` of the client was rejected, e.g. due to failed authentication.
+    /// the connection is closed afterwards
+    HelloRejected {
+        reason:String
     },
     Instances {
         instances:Vec<InstanceInfo>
@@ -55,7 +101,78 @@ pub enum ServerMsg {
         msg:Vec<u8>
     },
     JoinRejected {
+        instance:InstanceInfo,
+
+        /// why the join was rejected, e.g. the instance is full or refused by `Server::can_join`
+        reason:String
+    },
+    InstanceCreated {
+        instance:InstanceInfo
+    },
+    CreateInstanceRejected {
+        reason:String
+    },
+
+    /// the client has been kicked, e.g. because the same identity connected again or by the server of its instance.
+    /// the connection is closed afterwards, unless the server sent the client back to the lobby
+    Kicked {
+        reason:String
+    },
+
+    /// a message from the operators of the master, e.g. sent using the admin API
+    Announcement {
+        message:String
+    },
+
+    /// the master is shutting down. the connection is closed afterwards
+    ServerShuttingDown {
+        reason:String
+    },
+
+    /// the instance the client was in has ended and the client is back in the lobby
+    InstanceEnded {
+        instance:InstanceInfo,
+        reason:String
+    },
+
+    /// an instance was created while the client is in the lobby
+    InstanceAdded {
+        instance:InstanceInfo
+    },
+
+    /// the info of an instance changed while the client is in the lobby, e.g. its number of players
+    InstanceUpdated {
         instance:InstanceInfo
+    },
+
+    /// an instance ended or was removed while the client is in the lobby
+    InstanceRemoved {
+        instance_id:Uuid
+    },
+
+    /// sent after `JoinedLobby` if the master has a UDP side channel. the client registers its address
+    /// by sending `UdpMsg::Hello` with `key` to `port` on the host of the master, until answered by
+    /// `UdpMsg::Welcome`. until then unreliable messages are sent over the connection instead
+    UdpAvailable {
+        port:u16,
+        key:Uuid
+    }
+}
+
+/// datagram sent over the UDP side channel
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum UdpMsg {
+    /// sent by the client to register its address, and repeated every few seconds to keep it registered
+    Hello {
+        key:Uuid
+    },
+
+    /// sent by the master in reply to `Hello`
+    Welcome,
+
+    /// an unreliable custom message from the server, to be handled like `ServerMsg::Custom`
+    Custom {
+        msg:Vec<u8>
     }
 }
 
@@ -64,4 +181,60 @@ impl Bincoded for ClientMsg {
 }
 
 impl Bincoded for ServerMsg {
-}
\ No newline at end of file
+}
+
+impl Bincoded for UdpMsg {
+}
+
+impl ClientMsg {
+    /// wraps a typed message for the `ClientInput` of a `TypedServer`
+    pub fn custom<T:Bincoded>(msg:&T) -> Self {
+        ClientMsg::CustomMsg {
+            msg:msg.to_bincode()
+        }
+    }
+}
+
+impl ServerMsg {
+    /// decodes a `Custom` message into the `ServerOutput` of a `TypedServer`
+    ///
+    /// returns `None` if this is not a `Custom` message
+    pub fn decode_custom<T:Bincoded>(&self) -> Option<Result<T, String>> {
+        match self {
+            ServerMsg::Custom { msg } => Some(bincode::deserialize::<T>(msg).map_err(|err| err.to_string())),
+            _ => None
+        }
+    }
+}
+
+/// the framing of both directions of a connection on the client side, following the handshake.
+/// reset it when reconnecting
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ClientFraming {
+    outgoing:Framing,
+    incoming:Framing
+}
+
+impl ClientFraming {
+    /// encodes a message to the master. all messages after a `Hello` offering compression are framed
+    pub fn encode(&mut self, msg:&ClientMsg) -> Vec<u8> {
+        let bytes = self.outgoing.encode(msg.to_bincode());
+        if let ClientMsg::Hello { compression, .. } = msg {
+            self.outgoing.framed = !compression.is_empty();
+        }
+
+        bytes
+    }
+
+    /// decodes a message of the master. all messages after a `JoinedLobby` choosing a codec are framed,
+    /// and the messages to the master are compressed with it
+    pub fn decode(&mut self, frame:Vec<u8>) -> Option<ServerMsg> {
+        let msg = ServerMsg::from_bincode(&self.incoming.decode(frame)?)?;
+        if let ServerMsg::JoinedLobby { compression, .. } = &msg {
+            self.incoming.framed = compression.is_some();
+            self.outgoing.codec = *compression;
+        }
+
+        Some(msg)
+    }
+}
@@ -0,0 +1,263 @@
+use super::{Bincoded, ClientFraming, ClientMsg, ServerMsg, UdpMsg};
+pub use crate::frame::{FrameSink, FrameStream};
+use futures_util::{future::BoxFuture, SinkExt, StreamExt};
+use std::{io, marker::PhantomData, net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
+use tokio::{
+    net::UdpSocket,
+    select,
+    sync::{Notify, RwLock},
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+/// a connection to the master, as established by a `Transport`
+pub struct Connection {
+    pub sink: FrameSink,
+    pub stream: FrameStream,
+
+    /// address of the master, on whose host the UDP side channel offered with `ServerMsg::UdpAvailable`
+    /// is joined. `None` keeps receiving unreliable messages over the connection
+    pub peer: Option<SocketAddr>,
+}
+
+/// how a `NativeClient` connects to the master, everything after connecting is shared by all transports
+pub trait Transport: Send + Sync + 'static {
+    /// connects to the master, called again after every disconnect
+    fn connect(&self) -> BoxFuture<'_, io::Result<Connection>>;
+}
+
+/// state shared between a client and its reader task
+#[derive(Default)]
+struct Shared {
+    notify: Notify,
+    messages: RwLock<Vec<ServerMsg>>,
+    is_connected: RwLock<bool>,
+    is_udp_connected: RwLock<bool>,
+    udp: Mutex<Option<JoinHandle<()>>>,
+    sink: RwLock<Option<FrameSink>>,
+    framing: Mutex<ClientFraming>,
+}
+
+/// a native client, connecting to the master using the transport `T`, such as `TungsteniteClient` and `TcpClient`
+///
+/// joins the UDP side channel offered by the master with `ServerMsg::UdpAvailable` if the transport allows it,
+/// unreliable messages received over it are returned as `ServerMsg::Custom` together with the other messages
+pub struct NativeClient<T: Transport> {
+    shared: Arc<Shared>,
+    reader: JoinHandle<()>,
+    transport: PhantomData<T>,
+}
+
+/// registers with the UDP side channel of the master at `addr` and receives datagrams
+/// until aborted, keeping the registration alive
+async fn run_udp(addr: SocketAddr, key: Uuid, shared: Arc<Shared>) {
+    let local: SocketAddr = match addr {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+    let socket = match UdpSocket::bind(local).await {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+    if socket.connect(addr).await.is_err() {
+        return;
+    }
+
+    let hello = UdpMsg::Hello { key }.to_bincode();
+    let mut buf = vec![0; 2048];
+    loop {
+        let _ = socket.send(&hello).await;
+
+        // retry quickly until welcomed, then keep the registration alive
+        let wait = match *shared.is_udp_connected.read().await {
+            true => Duration::from_secs(5),
+            false => Duration::from_millis(250),
+        };
+        let until = tokio::time::Instant::now() + wait;
+        loop {
+            select! {
+                _ = tokio::time::sleep_until(until) => break,
+                len = socket.recv(&mut buf) => {
+                    match len.ok().and_then(|len| UdpMsg::from_bincode(&buf[..len])) {
+                        Some(UdpMsg::Welcome) => {
+                            *shared.is_udp_connected.write().await = true;
+                        }
+                        Some(UdpMsg::Custom { msg }) => {
+                            shared.messages.write().await.push(ServerMsg::Custom { msg });
+                            shared.notify.notify_one();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn spawn_reader<T: Transport>(transport: T, shared: Arc<Shared>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let Connection { sink, mut stream, peer } = match transport.connect().await {
+                Ok(connection) => connection,
+                Err(_) => {
+                    // failed, wait a bit and try again
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            *shared.is_connected.write().await = true;
+            *shared.framing.lock().unwrap() = ClientFraming::default();
+            *shared.sink.write().await = Some(sink);
+            shared.notify.notify_one();
+
+            while let Some(Ok(frame)) = stream.next().await {
+                let msg = match shared.framing.lock().unwrap().decode(frame) {
+                    Some(msg) => msg,
+                    None => break,
+                };
+                if let (ServerMsg::UdpAvailable { port, key }, Some(peer)) = (&msg, peer) {
+                    let mut addr = peer;
+                    addr.set_port(*port);
+                    let task = tokio::spawn(run_udp(addr, *key, shared.clone()));
+                    if let Some(previous) = shared.udp.lock().unwrap().replace(task) {
+                        previous.abort();
+                    }
+                }
+
+                shared.messages.write().await.push(msg);
+                shared.notify.notify_one();
+            }
+
+            if let Some(udp) = shared.udp.lock().unwrap().take() {
+                udp.abort();
+            }
+            *shared.is_udp_connected.write().await = false;
+            *shared.is_connected.write().await = false;
+            *shared.sink.write().await = None;
+            shared.notify.notify_one();
+        }
+    })
+}
+
+impl<T: Transport> NativeClient<T> {
+    /// instantiate a Client using `transport`
+    ///
+    /// Will automatically try to connect to the server and will try re-establish connection
+    /// in case of a disconnect
+    pub fn with_transport(transport: T) -> Self {
+        let shared = Arc::new(Shared::default());
+        let reader = spawn_reader(transport, shared.clone());
+        Self {
+            shared,
+            reader,
+            transport: PhantomData,
+        }
+    }
+
+    /// returns true if currently connected
+    pub async fn is_connected(&self) -> bool {
+        *self.shared.is_connected.read().await
+    }
+
+    /// returns true if the master accepted the UDP side channel of the client,
+    /// i.e. unreliable messages are received over UDP
+    pub async fn is_udp_connected(&self) -> bool {
+        *self.shared.is_udp_connected.read().await
+    }
+
+    /// waits until successfully connected
+    pub async fn connect(&self) {
+        while !self.is_connected().await {
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// sends a message to the server
+    /// returns true if the message was successfully sent
+    pub async fn send(&mut self, msg: ClientMsg) -> bool {
+        match &mut *self.shared.sink.write().await {
+            Some(sink) => {
+                let msg = self.shared.framing.lock().unwrap().encode(&msg);
+                sink.send(msg).await.is_ok()
+            }
+            None => false,
+        }
+    }
+
+    /// sends a typed message to a `TypedServer`
+    /// returns true if the message was successfully sent
+    pub async fn send_custom<M: Bincoded>(&mut self, msg: &M) -> bool {
+        self.send(ClientMsg::custom(msg)).await
+    }
+
+    /// gets the typed messages sent by a `TypedServer`, leaving all other messages queued for `messages`
+    /// waits for at least one typed message
+    ///
+    /// messages which cannot be decoded are returned as `Err`
+    ///
+    /// returns `None` in case of a disconnect
+    pub async fn custom_messages<M: Bincoded>(&self) -> Option<Vec<Result<M, String>>> {
+        loop {
+            {
+                let mut messages = self.shared.messages.write().await;
+                let mut custom = Vec::new();
+                messages.retain(|msg| match msg.decode_custom::<M>() {
+                    Some(msg) => {
+                        custom.push(msg);
+                        false
+                    }
+                    None => true,
+                });
+                if !custom.is_empty() {
+                    return Some(custom);
+                }
+            }
+
+            if !self.is_connected().await {
+                return None;
+            }
+
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// gets a list of messages received from the server
+    /// waits for at least one message
+    ///
+    /// returns `None` in case of a disconnect
+    pub async fn messages(&self) -> Option<Vec<ServerMsg>> {
+        loop {
+            {
+                let mut messages = self.shared.messages.write().await;
+                if !messages.is_empty() {
+                    return Some(std::mem::take(&mut *messages));
+                }
+            }
+
+            if !self.is_connected().await {
+                return None;
+            }
+
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// polls the currently available messages
+    /// returns None in case of a disconnect
+    pub async fn poll_messages(&mut self) -> Option<Vec<ServerMsg>> {
+        match self.is_connected().await {
+            true => Some(std::mem::take(&mut *self.shared.messages.write().await)),
+            false => None,
+        }
+    }
+}
+
+impl<T: Transport> Drop for NativeClient<T> {
+    fn drop(&mut self) {
+        self.reader.abort();
+        if let Some(udp) = self.shared.udp.lock().unwrap().take() {
+            udp.abort();
+        }
+    }
+}
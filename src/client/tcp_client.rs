@@ -0,0 +1,38 @@
+use super::native_client::{Connection, NativeClient, Transport};
+use crate::frame::tcp;
+use futures_util::{future::BoxFuture, FutureExt};
+use std::io;
+use tokio::net::TcpStream;
+
+/// connects to `master::Config::tcp_addr` using raw TCP instead of WebSockets
+pub struct Tcp {
+    addr: String,
+}
+
+impl Transport for Tcp {
+    fn connect(&self) -> BoxFuture<'_, io::Result<Connection>> {
+        async move {
+            let stream = TcpStream::connect(&self.addr).await?;
+            let peer = stream.peer_addr().ok();
+            let (sink, stream) = tcp(stream);
+            Ok(Connection { sink, stream, peer })
+        }
+        .boxed()
+    }
+}
+
+/// a native client connecting to `master::Config::tcp_addr` using raw TCP instead of WebSockets,
+/// with the same interface as `TungsteniteClient`
+pub type TcpClient = NativeClient<Tcp>;
+
+impl TcpClient {
+    /// instantiate a Client using raw TCP
+    ///
+    /// `addr` is the address of the master, such as "localhost:1235"
+    ///
+    /// Will automatically try to connect to the server and will try re-establish connection
+    /// in case of a disconnect
+    pub fn new(addr: &str) -> Self {
+        Self::with_transport(Tcp { addr: addr.into() })
+    }
+}
@@ -0,0 +1,92 @@
+use std::convert::TryInto;
+
+use serde::{Deserialize, Serialize};
+
+/// largest message accepted after decompression, protecting against decompression bombs
+pub const MAX_DECOMPRESSED_LEN:usize = 16 * 1024 * 1024;
+
+/// flag byte in front of a message which is sent as is
+const RAW:u8 = 0;
+
+/// flag byte in front of a message compressed with LZ4, prefixed with its uncompressed length
+const LZ4:u8 = 1;
+
+/// compression algorithms a client can offer in `ClientMsg::Hello`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    Lz4
+}
+
+/// the compression chosen by the master for a connection, sent in `ServerMsg::JoinedLobby`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Codec {
+    pub compression:Compression,
+
+    /// messages shorter than this many bytes are not compressed
+    pub threshold:u32
+}
+
+/// how messages are encoded in one direction of a connection.
+///
+/// once compression has been offered in `Hello`, every message from the client starts with a flag byte
+/// telling whether it is compressed. the same holds for messages from the master after a `JoinedLobby`
+/// which chose a `Codec`. either side only compresses with a `Codec` chosen by the master
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Framing {
+    /// messages start with a flag byte
+    pub framed:bool,
+
+    /// compresses messages reaching the threshold
+    pub codec:Option<Codec>
+}
+
+impl Framing {
+    /// encodes `msg` for sending
+    pub fn encode(&self, msg:Vec<u8>) -> Vec<u8> {
+        if !self.framed {
+            return msg;
+        }
+
+        if let Some(codec) = self.codec {
+            if msg.len() >= codec.threshold as usize {
+                let compressed = match codec.compression {
+                    Compression::Lz4 => lz4_flex::compress_prepend_size(&msg)
+                };
+
+                // incompressible data is sent as is
+                if compressed.len() < msg.len() {
+                    let mut frame = Vec::with_capacity(compressed.len() + 1);
+                    frame.push(LZ4);
+                    frame.extend_from_slice(&compressed);
+                    return frame;
+                }
+            }
+        }
+
+        let mut frame = Vec::with_capacity(msg.len() + 1);
+        frame.push(RAW);
+        frame.extend_from_slice(&msg);
+        frame
+    }
+
+    /// decodes a received message, returning `None` if it is malformed
+    pub fn decode(&self, frame:Vec<u8>) -> Option<Vec<u8>> {
+        if !self.framed {
+            return Some(frame);
+        }
+
+        let (flag, msg) = frame.split_first()?;
+        match *flag {
+            RAW => Some(msg.to_vec()),
+            LZ4 => {
+                let len = u32::from_le_bytes(msg.get(0..4)?.try_into().ok()?) as usize;
+                if len > MAX_DECOMPRESSED_LEN {
+                    return None;
+                }
+
+                lz4_flex::decompress_size_prepended(msg).ok()
+            },
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::{io, pin::Pin};
+
+use futures_util::{Sink, Stream};
+use tokio::{io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt}, net::TcpStream};
+
+/// sends encoded messages to the other side, regardless of the transport
+pub type FrameSink = Pin<Box<dyn Sink<Vec<u8>, Error = io::Error> + Send + Sync>>;
+
+/// receives encoded messages from the other side, regardless of the transport
+pub type FrameStream = Pin<Box<dyn Stream<Item = Result<Vec<u8>, io::Error>> + Send + Sync>>;
+
+/// largest frame accepted from the other side, larger frames close the connection
+pub const MAX_FRAME_LEN:usize = 16 * 1024 * 1024;
+
+/// writes `frame` prefixed with its length as big endian u32
+pub async fn write_frame<W:AsyncWrite + Unpin>(writer:&mut W, frame:&[u8]) -> io::Result<()> {
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame too large"));
+    }
+
+    let mut buf = Vec::with_capacity(4 + frame.len());
+    buf.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+    buf.extend_from_slice(frame);
+    writer.write_all(&buf).await?;
+    writer.flush().await
+}
+
+/// reads a frame written by `write_frame`, returning `None` if the connection was closed between frames
+pub async fn read_frame<R:AsyncRead + Unpin>(reader:&mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {},
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err)
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(Some(frame))
+}
+
+/// frames are prefixed with their length as big endian u32
+pub(crate) fn tcp(stream:TcpStream) -> (FrameSink, FrameStream) {
+    let _ = stream.set_nodelay(true);
+    let (reader, writer) = stream.into_split();
+    let sink = futures_util::sink::unfold(writer, |mut writer, frame:Vec<u8>| async move {
+        write_frame(&mut writer, &frame).await?;
+        Ok::<_, io::Error>(writer)
+    });
+    let stream = futures_util::stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        match read_frame(&mut reader).await {
+            Ok(Some(frame)) => Some((Ok(frame), Some(reader))),
+            Ok(None) => None,
+            // end the stream after an error, the connection is unusable
+            Err(err) => Some((Err(err), None))
+        }
+    });
+
+    (Box::pin(sink), Box::pin(stream))
+}
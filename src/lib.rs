@@ -17,5 +17,23 @@ all types needed for a client.
 valid both for native targets and wasm32 targets.
 */
 pub mod client;
+
+#[cfg(not(target_arch = "wasm32"))]
+/**
+a harness for testing game servers without a master or sockets.
+only valid for native targets, i.e. non-wasm32
+*/
+pub mod testing;
+
+#[cfg(not(target_arch = "wasm32"))]
+/**
+length prefixed framing of the messages sent over raw TCP
+*/
+mod frame;
+/**
+compression of messages, negotiated between clients and the master.
+valid both for native targets and wasm32 targets.
+*/
+pub mod compression;
 pub mod bincoded;
 pub mod shared;
\ No newline at end of file
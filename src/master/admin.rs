@@ -0,0 +1,280 @@
+use std::convert::Infallible;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::{Filter, Rejection, Reply, http::StatusCode, reject::Reject, reply};
+
+use crate::shared::InstanceInfo;
+
+use super::Master;
+
+/// an instance as listed by the admin API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminInstance {
+    #[serde(flatten)]
+    pub info:InstanceInfo,
+    pub players:Vec<AdminPlayer>,
+
+    /// the panic message of the last crash of the server, if any
+    pub last_crash:Option<String>
+}
+
+/// a crash of the server of an instance as listed by the admin API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminCrash {
+    pub instance:Uuid,
+
+    /// the panic message
+    pub message:String,
+
+    /// true if the instance was restarted, false if it ended
+    pub restarted:bool
+}
+
+/// a client seated in an instance as listed by the admin API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminPlayer {
+    pub client_id:Uuid,
+    pub client_name:String,
+
+    /// false if the client has disconnected and its seat is held
+    pub connected:bool
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CreateInstance {
+    kind:Option<String>,
+    creator:Option<Uuid>,
+    settings:Option<Vec<u8>>
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Reason {
+    reason:Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+struct Broadcast {
+    message:String
+}
+
+#[derive(Debug)]
+struct Unauthorized;
+impl Reject for Unauthorized {}
+
+fn error(status:StatusCode, message:&str) -> reply::WithStatus<reply::Json> {
+    #[derive(Serialize)]
+    struct Error<'a> {
+        error:&'a str
+    }
+
+    reply::with_status(reply::json(&Error { error:message }), status)
+}
+
+fn ok() -> reply::WithStatus<reply::Json> {
+    reply::with_status(reply::json(&"ok"), StatusCode::OK)
+}
+
+async fn list_instances(master:Master) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    let instances = master.lobby.read().await.all_instances();
+    let mut list = Vec::new();
+    for instance in instances {
+        let players = instance.players().await.into_iter().map(|player| AdminPlayer {
+            client_id:player.client_id,
+            client_name:player.client_name,
+            connected:player.connected
+        }).collect();
+        list.push(AdminInstance {
+            info:instance.info.read().await.clone(),
+            players,
+            last_crash:instance.last_crash()
+        });
+    }
+
+    Ok(reply::with_status(reply::json(&list), StatusCode::OK))
+}
+
+async fn list_crashes(master:Master) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    let crashes = master.lobby.read().await.crashes();
+    Ok(reply::with_status(reply::json(&crashes), StatusCode::OK))
+}
+
+async fn create_instance(mut master:Master, body:CreateInstance) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    let kind = body.kind.unwrap_or_else(|| master.config.default_kind.clone());
+    let creator = body.creator.unwrap_or_default();
+    let settings = body.settings.unwrap_or_default();
+    let id = match master.new_instance_with(&kind, creator, &settings).await {
+        Ok(id) => id,
+        Err(reason) => return Ok(error(StatusCode::BAD_REQUEST, &reason))
+    };
+
+    let instance = master.lobby.read().await.get_instance(id);
+    match instance {
+        Some(instance) => Ok(reply::with_status(reply::json(&*instance.info.read().await), StatusCode::CREATED)),
+        None => Ok(error(StatusCode::INTERNAL_SERVER_ERROR, "instance ended"))
+    }
+}
+
+async fn remove_instance(id:Uuid, mut master:Master) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    if master.remove_instance(id).await {
+        return Ok(ok());
+    }
+
+    Ok(error(StatusCode::NOT_FOUND, "unknown instance"))
+}
+
+async fn broadcast(id:Uuid, master:Master, body:Broadcast) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    let instance = master.lobby.read().await.get_instance(id);
+    match instance {
+        Some(instance) => {
+            instance.announce(&body.message).await;
+            Ok(ok())
+        },
+        None => Ok(error(StatusCode::NOT_FOUND, "unknown instance"))
+    }
+}
+
+async fn kick(client_id:Uuid, master:Master, body:Reason) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    match master.sessions.get(client_id) {
+        Some(session) => {
+            session.kick(body.reason.as_deref().unwrap_or("kicked by admin"));
+            Ok(ok())
+        },
+        None => Ok(error(StatusCode::NOT_FOUND, "client not connected"))
+    }
+}
+
+async fn ban(client_id:Uuid, master:Master, body:Reason) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    master.sessions.ban(client_id, body.reason.as_deref().unwrap_or("banned by admin"));
+    Ok(ok())
+}
+
+async fn unban(client_id:Uuid, master:Master) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    if master.sessions.unban(client_id) {
+        return Ok(ok());
+    }
+
+    Ok(error(StatusCode::NOT_FOUND, "client not banned"))
+}
+
+async fn rejection(err:Rejection) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    if err.find::<Unauthorized>().is_some() {
+        return Ok(error(StatusCode::UNAUTHORIZED, "invalid admin token"));
+    }
+
+    if err.is_not_found() {
+        return Ok(error(StatusCode::NOT_FOUND, "not found"));
+    }
+
+    if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        return Ok(error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"));
+    }
+
+    Ok(error(StatusCode::BAD_REQUEST, "bad request"))
+}
+
+/// compares without exiting early, such that the admin token cannot be guessed byte by byte
+/// from the response times. only the length of the token is revealed
+fn constant_time_eq(a:&[u8], b:&[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let diff = a.iter().zip(b).fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    std::hint::black_box(diff) == 0
+}
+
+/// body which is optional, defaulting when empty
+fn optional_json<T:Default + serde::de::DeserializeOwned + Send>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    warp::body::bytes().and_then(|bytes:warp::hyper::body::Bytes| async move {
+        if bytes.is_empty() {
+            return Ok(T::default());
+        }
+
+        serde_json::from_slice(&bytes).map_err(|_| warp::reject::reject())
+    })
+}
+
+/// the JSON admin API, served below `/admin`
+///
+/// every request must carry an `Authorization: Bearer <token>` header matching `token`.
+/// the API is disabled if `token` is `None`
+///
+/// - `GET /admin/instances` lists instances and their players
+/// - `GET /admin/crashes` lists the most recent crashes of servers, oldest first
+/// - `POST /admin/instances` with `{"kind":.., "creator":.., "settings":[..]}`, all optional, creates an instance
+/// - `DELETE /admin/instances/<id>` ends and removes an instance
+/// - `POST /admin/instances/<id>/broadcast` with `{"message":..}` sends `ServerMsg::Announcement` to its clients
+/// - `POST /admin/clients/<id>/kick` with optional `{"reason":..}` disconnects a client
+/// - `POST /admin/clients/<id>/ban` with optional `{"reason":..}` disconnects a client and rejects it from now on
+/// - `DELETE /admin/clients/<id>/ban` lifts a ban
+///
+/// bans require an `Authenticator` with stable client ids, see `Sessions::ban`
+pub(crate) fn routes(master:Master, token:Option<String>) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let enabled = token.is_some();
+    let enabled = warp::any()
+        .and_then(move || async move {
+            match enabled {
+                true => Ok(()),
+                false => Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one();
+    let expected = format!("Bearer {}", token.unwrap_or_default());
+    let authorized = warp::header::optional::<String>("authorization")
+        .and_then(move |authorization:Option<String>| {
+            let authorized = authorization.is_some_and(|authorization| constant_time_eq(authorization.as_bytes(), expected.as_bytes()));
+            async move {
+                match authorized {
+                    true => Ok(()),
+                    false => Err(warp::reject::custom(Unauthorized))
+                }
+            }
+        })
+        .untuple_one();
+    let master = warp::any().map(move || master.clone());
+
+    let list = warp::path!("instances")
+        .and(warp::get())
+        .and(master.clone())
+        .and_then(list_instances);
+    let create = warp::path!("instances")
+        .and(warp::post())
+        .and(master.clone())
+        .and(optional_json::<CreateInstance>())
+        .and_then(create_instance);
+    let crashes = warp::path!("crashes")
+        .and(warp::get())
+        .and(master.clone())
+        .and_then(list_crashes);
+    let remove = warp::path!("instances" / Uuid)
+        .and(warp::delete())
+        .and(master.clone())
+        .and_then(remove_instance);
+    let broadcast = warp::path!("instances" / Uuid / "broadcast")
+        .and(warp::post())
+        .and(master.clone())
+        .and(warp::body::json())
+        .and_then(broadcast);
+    let kick = warp::path!("clients" / Uuid / "kick")
+        .and(warp::post())
+        .and(master.clone())
+        .and(optional_json::<Reason>())
+        .and_then(kick);
+    let ban = warp::path!("clients" / Uuid / "ban")
+        .and(warp::post())
+        .and(master.clone())
+        .and(optional_json::<Reason>())
+        .and_then(ban);
+    let unban = warp::path!("clients" / Uuid / "ban")
+        .and(warp::delete())
+        .and(master)
+        .and_then(unban);
+
+    warp::path("admin")
+        .and(enabled)
+        .and(authorized
+            .and(list.or(create).unify().or(crashes).unify().or(remove).unify().or(broadcast).unify().or(kick).unify().or(ban).unify().or(unban).unify())
+            .recover(rejection)
+            .unify())
+}
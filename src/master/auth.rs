@@ -0,0 +1,160 @@
+use std::{collections::HashMap, path::Path};
+
+use futures_util::future::{self, BoxFuture, FutureExt};
+use uuid::Uuid;
+
+/// everything known about a connecting client when its `ClientMsg::Hello` is received
+#[derive(Clone, Debug)]
+pub struct AuthRequest {
+    /// the id claimed by the client in `Hello`
+    pub client_id:Uuid,
+
+    /// the name claimed by the client in `Hello`
+    pub client_name:String,
+
+    /// the token sent by the client in `Hello`
+    pub token:Option<String>,
+
+    /// query parameters of the WebSocket upgrade request, empty for raw TCP clients
+    pub query:HashMap<String, String>,
+
+    /// headers of the WebSocket upgrade request, with lowercase names. empty for raw TCP clients
+    pub headers:HashMap<String, String>
+}
+
+impl AuthRequest {
+    /// returns the token of the client, looking in order at
+    /// the `Hello` token, the `token` query parameter and the `Authorization: Bearer` header
+    pub fn token(&self) -> Option<&str> {
+        if let Some(token) = &self.token {
+            return Some(token);
+        }
+
+        if let Some(token) = self.query.get("token") {
+            return Some(token);
+        }
+
+        if let Some(authorization) = self.headers.get("authorization") {
+            if let Some(token) = authorization.strip_prefix("Bearer ") {
+                return Some(token);
+            }
+        }
+
+        None
+    }
+}
+
+/// the verified identity of a client, used for the rest of the session
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    pub client_id:Uuid,
+    pub client_name:String
+}
+
+/// decides if a client is allowed to join the lobby
+///
+/// called by the master when `ClientMsg::Hello` is received, before `ServerMsg::JoinedLobby` is sent.
+/// only the connecting client waits for the returned future, so implementations can await I/O
+/// such as a database or a remote identity provider
+pub trait Authenticator : Send + Sync + 'static {
+    /// resolves to the verified identity of the client or a reason for rejecting it
+    fn authenticate<'a>(&'a self, request:&'a AuthRequest) -> BoxFuture<'a, Result<Identity, String>>;
+}
+
+/// accepts every client, assigning each a new id and keeping the name sent in `Hello`.
+/// the id sent by the client is ignored, such that it cannot impersonate other clients.
+/// as every connection gets a fresh id, clients cannot be banned
+#[derive(Clone, Debug, Default)]
+pub struct AssignIds;
+
+impl Authenticator for AssignIds {
+    fn authenticate<'a>(&'a self, request:&'a AuthRequest) -> BoxFuture<'a, Result<Identity, String>> {
+        future::ready(Ok(Identity {
+            client_id:Uuid::new_v4(),
+            client_name:request.client_name.clone()
+        })).boxed()
+    }
+}
+
+/// accepts every client, trusting the id and name sent in `Hello`.
+/// only useful in trusted environments, as any client can claim any id
+#[derive(Clone, Debug, Default)]
+pub struct AllowAll;
+
+impl Authenticator for AllowAll {
+    fn authenticate<'a>(&'a self, request:&'a AuthRequest) -> BoxFuture<'a, Result<Identity, String>> {
+        future::ready(Ok(Identity {
+            client_id:request.client_id,
+            client_name:request.client_name.clone()
+        })).boxed()
+    }
+}
+
+/// accepts clients presenting one of a set of known tokens,
+/// each token mapping to a fixed identity
+#[derive(Clone, Debug, Default)]
+pub struct TokenAuthenticator {
+    tokens:HashMap<String, Identity>
+}
+
+impl TokenAuthenticator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a `token` which authenticates as `identity`
+    pub fn insert(&mut self, token:&str, identity:Identity) {
+        self.tokens.insert(token.into(), identity);
+    }
+
+    /// parses tokens from text with one token per line in the form
+    /// `<token> <client_id> <client_name>`, where the name is the rest of the line.
+    /// empty lines and lines starting with `#` are ignored
+    pub fn parse(text:&str) -> Result<Self, String> {
+        let mut auth = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let token = parts.next().unwrap_or_default();
+            let client_id = parts.next().and_then(|id| Uuid::parse_str(id).ok());
+            let client_name = parts.next().map(|name| name.trim());
+            match (client_id, client_name) {
+                (Some(client_id), Some(client_name)) => {
+                    auth.insert(token, Identity {
+                        client_id,
+                        client_name:client_name.into()
+                    });
+                },
+                _ => return Err(format!("invalid token on line {}", i + 1))
+            }
+        }
+
+        Ok(auth)
+    }
+
+    /// loads tokens from a file, see `parse` for the format
+    pub fn from_file<P:AsRef<Path>>(path:P) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&text)
+    }
+}
+
+impl TokenAuthenticator {
+    fn lookup(&self, request:&AuthRequest) -> Result<Identity, String> {
+        let token = request.token().ok_or("missing token")?;
+        match self.tokens.get(token) {
+            Some(identity) => Ok(identity.clone()),
+            None => Err("invalid token".into())
+        }
+    }
+}
+
+impl Authenticator for TokenAuthenticator {
+    fn authenticate<'a>(&'a self, request:&'a AuthRequest) -> BoxFuture<'a, Result<Identity, String>> {
+        future::ready(self.lookup(request)).boxed()
+    }
+}
@@ -0,0 +1,96 @@
+use std::{future::Future, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+
+use tokio::{runtime::{Builder, Handle}, sync::oneshot};
+
+/// where the instances of a master run their servers
+#[derive(Clone, Default)]
+pub enum Execution {
+    /// instances run as tasks on the tokio runtime of the master, next to the networking
+    #[default]
+    Runtime,
+
+    /// each instance runs on its own OS thread, such that a slow tick cannot stall networking
+    Thread,
+
+    /// instances run on a fixed pool of OS threads, shared round-robin
+    Pool(ThreadPool)
+}
+
+impl Execution {
+    /// runs the task of an instance according to the mode
+    pub(crate) fn spawn<F:Future<Output = ()> + Send + 'static>(&self, name:String, task:F) {
+        match self {
+            Execution::Runtime => {
+                tokio::spawn(task);
+            },
+            Execution::Thread => {
+                std::thread::Builder::new()
+                    .name(name)
+                    .spawn(move || runtime().block_on(task))
+                    .expect("could not spawn instance thread");
+            },
+            Execution::Pool(pool) => {
+                pool.spawn(task);
+            }
+        }
+    }
+}
+
+/// a pool of OS threads each running a single threaded tokio runtime
+///
+/// the threads stop once the pool and all its clones are dropped,
+/// cancelling the instances still running on them
+#[derive(Clone)]
+pub struct ThreadPool {
+    inner:Arc<Inner>
+}
+
+struct Inner {
+    handles:Vec<Handle>,
+    next:AtomicUsize,
+
+    /// dropping these stops the threads
+    _stop:Vec<oneshot::Sender<()>>
+}
+
+impl ThreadPool {
+    /// starts a pool of `threads` threads, at least one
+    pub fn new(threads:usize) -> Self {
+        let mut handles = Vec::new();
+        let mut stop = Vec::new();
+        for i in 0..threads.max(1) {
+            let (stop_tx, stop_rx) = oneshot::channel::<()>();
+            let (handle_tx, handle_rx) = std::sync::mpsc::channel();
+            std::thread::Builder::new()
+                .name(format!("hostess-pool-{}", i))
+                .spawn(move || {
+                    let runtime = runtime();
+                    let _ = handle_tx.send(runtime.handle().clone());
+                    let _ = runtime.block_on(stop_rx);
+                })
+                .expect("could not spawn pool thread");
+            handles.push(handle_rx.recv().expect("pool thread did not start"));
+            stop.push(stop_tx);
+        }
+
+        Self {
+            inner:Arc::new(Inner {
+                handles,
+                next:AtomicUsize::new(0),
+                _stop:stop
+            })
+        }
+    }
+
+    fn spawn<F:Future<Output = ()> + Send + 'static>(&self, task:F) {
+        let i = self.inner.next.fetch_add(1, Ordering::Relaxed) % self.inner.handles.len();
+        self.inner.handles[i].spawn(task);
+    }
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("could not build runtime")
+}
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use warp::{Filter, Reply, filters::BoxedFilter, http::{HeaderMap, StatusCode}, path::Tail};
+
+use super::{Master, admin, transport};
+
+/// where static files are served from
+#[derive(Clone, Debug, PartialEq)]
+pub enum StaticFiles {
+    /// no static files are served
+    Disabled,
+
+    /// files are served from the directory
+    Dir(PathBuf),
+
+    /// files are served from the directory, unknown paths are answered with its `index.html`
+    /// such that single page apps can do their own routing
+    Spa(PathBuf)
+}
+
+/// the HTTP routes of a master, built using `Http::new()` and set in `Config::http`
+#[derive(Clone, Debug)]
+pub struct Http {
+    pub(crate) ws_path:Option<String>,
+    pub(crate) static_files:StaticFiles,
+    pub(crate) allowed_origins:Option<Vec<String>>
+}
+
+impl Default for Http {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Http {
+    /// accepts WebSocket upgrades on any path and serves static files from `./public`
+    pub fn new() -> Self {
+        Self {
+            ws_path:None,
+            static_files:StaticFiles::Dir("./public".into()),
+            allowed_origins:None
+        }
+    }
+
+    /// accepts WebSocket upgrades only on `path`, e.g. `/ws`, relative to where the routes are mounted
+    pub fn ws_path(mut self, path:&str) -> Self {
+        self.ws_path = Some(normalize(path));
+        self
+    }
+
+    /// serves static files from `dir`
+    pub fn static_dir(mut self, dir:impl Into<PathBuf>) -> Self {
+        self.static_files = StaticFiles::Dir(dir.into());
+        self
+    }
+
+    /// serves static files from `dir`, answering unknown paths with its `index.html`
+    pub fn spa(mut self, dir:impl Into<PathBuf>) -> Self {
+        self.static_files = StaticFiles::Spa(dir.into());
+        self
+    }
+
+    /// serves no static files
+    pub fn no_static_files(mut self) -> Self {
+        self.static_files = StaticFiles::Disabled;
+        self
+    }
+
+    /// allows browsers on `origin`, e.g. `https://example.com`, to connect and to make cross-origin requests.
+    /// once an origin is allowed, WebSocket upgrades from other origins are refused.
+    /// upgrades without an `Origin` header, e.g. from native clients, are always accepted
+    pub fn allow_origin(mut self, origin:&str) -> Self {
+        self.allowed_origins.get_or_insert_with(Vec::new).push(origin.trim_end_matches('/').into());
+        self
+    }
+
+    /// returns true if a WebSocket upgrade from `origin` is accepted
+    fn is_allowed(&self, origin:Option<&str>) -> bool {
+        match (&self.allowed_origins, origin) {
+            (Some(allowed), Some(origin)) => allowed.iter().any(|allowed| allowed == origin),
+            _ => true
+        }
+    }
+}
+
+/// returns `path` with a single leading and no trailing slash
+fn normalize(path:&str) -> String {
+    format!("/{}", path.trim_matches('/'))
+}
+
+/// the HTTP routes of a master as a warp filter, see `Master::routes`
+pub type Routes = BoxedFilter<(Box<dyn Reply>,)>;
+
+/// builds the routes of `master` as configured in `Config::http`
+pub(crate) fn routes(master:Master) -> Routes {
+    let http = master.config.http.clone();
+
+    let ws_path = http.ws_path.clone();
+    // relative to where the routes are mounted
+    let on_ws_path = warp::path::tail()
+        .and_then(move |path:Tail| {
+            let matches = match &ws_path {
+                Some(ws_path) => normalize(path.as_str()) == *ws_path,
+                None => true
+            };
+            async move {
+                match matches {
+                    true => Ok(()),
+                    false => Err(warp::reject::not_found())
+                }
+            }
+        })
+        .untuple_one();
+
+    let ws_master = master.clone();
+    let ws_http = http.clone();
+    let ws_route = warp::get()
+        .and(on_ws_path)
+        .and(warp::ws())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::header::headers_cloned())
+        .map(move |ws:warp::ws::Ws, query:HashMap<String, String>, headers:HeaderMap| -> Box<dyn Reply> {
+            let origin = headers.get("origin").and_then(|origin| origin.to_str().ok());
+            if !ws_http.is_allowed(origin) {
+                return Box::new(StatusCode::FORBIDDEN);
+            }
+
+            let master = ws_master.clone();
+            let headers = headers.iter()
+                .filter_map(|(name, value)| Some((name.as_str().to_lowercase(), value.to_str().ok()?.to_string())))
+                .collect();
+            Box::new(ws.on_upgrade(move |ws| {
+                let (sink, stream) = transport::websocket(ws);
+                Master::client_connected(sink, stream, master, query, headers)
+            }))
+        });
+
+    let admin_route = admin::routes(master.clone(), master.config.admin_token.clone());
+    let metrics_master = master.clone();
+    let metrics_route = warp::path!("metrics")
+        .and(warp::get())
+        .and_then(move || {
+            let master = metrics_master.clone();
+            async move {
+                if !master.config.metrics {
+                    return Err(warp::reject::not_found());
+                }
+
+                Ok::<_, warp::Rejection>(master.render_metrics().await)
+            }
+        });
+
+    let routes = admin_route.map(boxed)
+        .or(metrics_route.map(boxed)).unify()
+        .or(ws_route).unify();
+    let routes = match http.static_files {
+        StaticFiles::Disabled => routes.boxed(),
+        StaticFiles::Dir(dir) => routes
+            .or(warp::fs::dir(dir).map(boxed)).unify()
+            .boxed(),
+        StaticFiles::Spa(dir) => routes
+            .or(warp::fs::dir(dir.clone()).map(boxed)).unify()
+            .or(warp::get().and(warp::fs::file(dir.join("index.html"))).map(boxed)).unify()
+            .boxed()
+    };
+
+    match http.allowed_origins {
+        Some(origins) => {
+            let cors = warp::cors()
+                .allow_origins(origins.iter().map(|origin| origin.as_str()))
+                .allow_methods(vec!["GET", "POST", "DELETE"])
+                .allow_headers(vec!["authorization", "content-type"]);
+            routes.with(cors).map(boxed).boxed()
+        },
+        None => routes
+    }
+}
+
+fn boxed(reply:impl Reply + 'static) -> Box<dyn Reply> {
+    Box::new(reply)
+}
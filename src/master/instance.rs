@@ -1,138 +1,440 @@
-use std::{collections::{HashMap, VecDeque}, sync::Arc, time::{Duration, Instant}};
+use std::{collections::{HashMap, HashSet, VecDeque}, future::pending, panic::AssertUnwindSafe, path::Path, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}, time::Duration};
 
 use futures_util::{FutureExt, pin_mut};
-use tokio::{sync::{RwLock, mpsc::Sender, mpsc::channel}, time::{MissedTickBehavior, interval}};
+use tokio::{sync::{RwLock, watch, mpsc::Sender, mpsc::channel}, time::{Instant, MissedTickBehavior, interval, interval_at, sleep_until}};
 use uuid::Uuid;
-use log::{info};
+use log::{error, info};
 use tokio::select;
-use crate::{shared::{InstanceInfo}, server};
+use crate::{shared::{InstanceInfo}, server, master};
 
-use crate::{client::{ClientMsg, ServerMsg}, server::{Constructor, Ctx, InMsg}, master::{ClientSink, Client}};
+use crate::{client::{ClientMsg, ServerMsg}, server::{Constructor, Ctx, Idle, InMsg, Timestep, clamp_tick_period}, master::{AdminCrash, ClientSink, Client, Metrics, RestartPolicy, snapshot::{self, Snapshot}}};
 
+#[allow(clippy::enum_variant_names)]
 enum Msg {
     InstanceMsg(InMsg),
     ClientTransfer {
         client_id:Uuid,
         client_name:String,
         sink:ClientSink,
-        return_sink:tokio::sync::oneshot::Sender<ClientSink>
+        return_sink:tokio::sync::oneshot::Sender<ClientSink>,
+
+        /// data sent by the client in `ClientMsg::JoinInstance`
+        payload:Vec<u8>,
+
+        /// true if the client is resuming a held seat
+        resume:bool
+    },
+    ClientDisconnected {
+        client_id:Uuid,
+
+        /// answered once the seat is held
+        held:tokio::sync::oneshot::Sender<()>
     },
     Ping {
         client_id:Uuid,
         tick:f64
+    },
+    End {
+        reason:String,
+
+        /// keep a final snapshot around, such that the instance is restored when the master starts again
+        suspend:bool
+    },
+    Players {
+        reply:tokio::sync::oneshot::Sender<Vec<Player>>
+    },
+    Announce {
+        message:String
     }
 }
+
+/// a client seated in an instance
+#[derive(Clone, Debug)]
+pub struct Player {
+    pub client_id:Uuid,
+    pub client_name:String,
+
+    /// false if the client has disconnected and its seat is held
+    pub connected:bool
+}
 #[derive(Clone)]
 pub struct Instance {
     pub info:Arc<RwLock<InstanceInfo>>,
     sender:Sender<Msg>,
+
+    /// clients which have disconnected but whose seat is held
+    held_seats:Arc<Mutex<HashSet<Uuid>>>,
+    reconnect_grace:Option<Duration>,
+
+    /// the panic message of the last crash of the server
+    last_crash:Arc<Mutex<Option<String>>>,
+
+    /// set once the server crashed without being restarted, ending the instance
+    crashed:Arc<AtomicBool>
 }
 
 impl Instance {
-    pub fn new(info:Arc<RwLock<InstanceInfo>>, constructor:Constructor) -> Self {
+    /// constructs the server and starts the instance task.
+    /// the server is restored from `snapshot` if given, failing if the server cannot restore it
+    pub fn new(mut info:InstanceInfo, constructor:Constructor, snapshot:Option<&[u8]>, master_config:&master::Config, metrics:Arc<Metrics>, lobby_changed:Arc<watch::Sender<()>>, crashes:Arc<Mutex<VecDeque<AdminCrash>>>) -> Result<Self, String> {
         let buffer_len = 1024;
         let (sender, mut receiver) = channel::<Msg>(buffer_len);
 
+        // construct and init the server up front, such that the info
+        // is complete once the instance is visible in the lobby.
+        // a panicking server fails the creation instead of unwinding the caller
+        let (mut g, mut config) = catch_panic(|| {
+            let mut g = constructor.construct(&info.settings)?;
+            let config = g.init();
+            if let Some(snapshot) = snapshot {
+                g.restore(snapshot)?;
+            }
+            Ok((g, config))
+        }).and_then(|res| res)?;
+        info.current_players = 0;
+        info.max_players = config.max_players;
+        info.crashed = false;
+        let settings = info.settings.clone();
+        let id = info.id;
+        let info = Arc::new(RwLock::new(info));
+
+        let held_seats = Arc::new(Mutex::new(HashSet::new()));
+        let last_crash = Arc::new(Mutex::new(None));
+        let crashed = Arc::new(AtomicBool::new(false));
         let instance = Self {
             info:info.clone(),
             sender,
+            held_seats:held_seats.clone(),
+            reconnect_grace:master_config.reconnect_grace,
+            last_crash:last_crash.clone(),
+            crashed:crashed.clone()
         };
 
-        tokio::spawn(async move {
-            let mut g = constructor.construct();
-            let config = g.init();
-            {
-                let mut instance = info.write().await;
-                instance.current_players = 0;
-                instance.max_players = config.max_players;
-            }
-
-            let period = Duration::from_millis(1000 / config.tick_rate);
+        let empty_timeout = master_config.empty_instance_timeout;
+        let snapshot_dir = master_config.snapshot_dir.clone();
+        let snapshot_interval = master_config.snapshot_interval;
+        let reconnect_grace = master_config.reconnect_grace.unwrap_or_default();
+        let restart_policy = master_config.restart_policy;
+        master_config.execution.spawn(format!("instance-{}", id), async move {
+            let period = clamp_tick_period(config.tick_period);
             let mut timer = interval(period);
             timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
             let mut context = Ctx {
                 out_messages:VecDeque::new(),
                 in_messages:VecDeque::with_capacity(buffer_len),
-                delta:timer.period().as_secs_f64(),
-                time:0.0
+                delta:period.as_secs_f64(),
+                time:0.0,
+                tick:0,
+                tick_period:period,
+                ended:None
             };
 
+            // the period the timer currently ticks with, `None` while sleeping
+            let mut ticking = Some(period);
+
+            // true while the timer wakes up with the idle period instead of the tick period
+            let mut idling = false;
+
+            // when the next tick is due with a fixed timestep, never moved by idle wakeups
+            let mut next_due = Instant::now();
+
+            // the timer keeps its schedule when ticks are dropped, it has to be moved to `next_due`
+            let mut realign = false;
+
             let mut clients:HashMap<Uuid, (ClientSink, tokio::sync::oneshot::Sender<ClientSink>)> = HashMap::new();
 
+            let mut names:HashMap<Uuid, String> = HashMap::new();
+
+            // disconnected clients and when their held seat expires
+            let mut disconnected:HashMap<Uuid, Instant> = HashMap::new();
+
             let mut last_tick = Instant::now();
-            loop {
-                let timer = timer.tick().fuse();//.await;
+            let mut empty_since = Instant::now();
+            let mut last_snapshot = Instant::now();
+            let mut suspended = false;
+            let mut restarts = 0;
+            let reason = loop {
+                // follow changes of the tick period and idle while no clients are seated
+                let wanted = match (clients.is_empty(), config.idle) {
+                    (true, Idle::Period(period)) => Some(clamp_tick_period(period)),
+                    (true, Idle::Sleep) => None,
+                    _ => Some(context.tick_period)
+                };
+                let idle = clients.is_empty() && config.idle != Idle::Tick;
+                if wanted != ticking || idle != idling {
+                    if let Some(period) = wanted {
+                        let start = Instant::now() + period;
+                        if !idle {
+                            next_due = start;
+                        }
+                        timer = interval_at(start, period);
+                        timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    }
+
+                    ticking = wanted;
+                    idling = idle;
+                } else if realign && ticking.is_some() {
+                    timer.reset_at(next_due);
+                }
+                realign = false;
+
+                // a sleeping instance still wakes up to be reaped when empty for too long
+                let wake = match ticking {
+                    Some(_) => None,
+                    None => empty_timeout.map(|timeout| empty_since + timeout)
+                };
+                let timer = async {
+                    match ticking {
+                        Some(_) => {
+                            timer.tick().await;
+                        },
+                        None => pending().await
+                    }
+                }.fuse();
+                let reap = async {
+                    match wake {
+                        Some(wake) => sleep_until(wake).await,
+                        None => pending().await
+                    }
+                }.fuse();
                 let recv = receiver.recv().fuse();
-                pin_mut!(timer, recv);
+                pin_mut!(timer, reap, recv);
                 select! {
+                    _ = reap => {
+                        // only reaps, a sleeping instance must not tick
+                        break "instance was empty for too long".into();
+                    },
                     _ = timer => {
                         let now = Instant::now();
-                        let diff = now - last_tick;
-                        context.delta = diff.as_secs_f64();
-                        context.time += context.delta;
-                        g.tick(&mut context);
-                        for msg in context.out_messages.drain(..) {
-                            match msg {
-                                server::OutMsg::CustomToAll { msg } => {
-                                    for (sink, _) in &mut clients.values_mut() {
-                                        let _ = sink.send(ServerMsg::Custom{
-                                            msg:msg.clone()
-                                        }).await;
-                                    }
-                                },
-                                server::OutMsg::CustomTo { client_id, msg } => {
-                                    if let Some((sink, _)) = clients.get_mut(&client_id) {
-                                        let _ = sink.send(ServerMsg::Custom{
-                                            msg:msg.clone()
-                                        }).await;
-                                    }
-                                },
+
+                        // release seats which were not resumed in time
+                        let seated = clients.len();
+                        disconnected.retain(|client_id, expires| {
+                            if *expires > now {
+                                return true;
                             }
+
+                            clients.remove(client_id);
+                            names.remove(client_id);
+                            held_seats.lock().unwrap().remove(client_id);
+                            context.in_messages.push_back(InMsg::ClientLeft {
+                                client_id:*client_id
+                            });
+                            false
+                        });
+                        if clients.len() != seated {
+                            info.write().await.current_players = clients.len() as u32;
+                            lobby_changed.send_replace(());
                         }
 
-                        context.in_messages.clear();
+                        let period = context.tick_period;
+                        let (ticks, delta) = match config.timestep {
+                            Timestep::Variable => (1, (now - last_tick).as_secs_f64()),
+
+                            // an idle wakeup runs a single tick, nothing is caught up
+                            Timestep::Fixed { .. } if idling => (1, period.as_secs_f64()),
+                            Timestep::Fixed { max_catch_up } => {
+                                // run the ticks which are due, dropping those beyond `max_catch_up`
+                                let missed = (now.saturating_duration_since(next_due).as_secs_f64() / period.as_secs_f64()) as u32;
+                                let ticks = missed.min(max_catch_up) + 1;
+                                next_due = match missed > max_catch_up {
+                                    true => {
+                                        realign = true;
+                                        now + period
+                                    },
+                                    false => next_due + period * ticks
+                                };
+                                (ticks, period.as_secs_f64())
+                            }
+                        };
+
+                        let mut crash = None;
+                        for _ in 0..ticks {
+                            context.delta = delta;
+                            context.time += delta;
+                            let tick_start = Instant::now();
+                            let res = catch_panic(|| g.tick(&mut context));
+                            metrics.tick(tick_start.elapsed(), period);
+                            if let Err(message) = res {
+                                crash = Some(message);
+                                break;
+                            }
+                            context.tick += 1;
+                            context.in_messages.clear();
+
+                            let kicked = send_out_messages(&mut context, &mut clients).await;
+                            if !kicked.is_empty() {
+                                for client_id in kicked {
+                                    names.remove(&client_id);
+                                    disconnected.remove(&client_id);
+                                    held_seats.lock().unwrap().remove(&client_id);
+                                    context.in_messages.push_back(InMsg::ClientLeft {
+                                        client_id
+                                    });
+                                }
+
+                                info.write().await.current_players = clients.len() as u32;
+                                lobby_changed.send_replace(());
+                            }
+
+                            if context.ended.is_some() {
+                                break;
+                            }
+                        }
 
                         last_tick = Instant::now();
+
+                        if let Some(message) = crash {
+                            let restart = match restart_policy {
+                                RestartPolicy::Never => false,
+                                RestartPolicy::Limited { max_restarts } => restarts < max_restarts
+                            };
+                            let id = info.read().await.id;
+                            error!("Instance {} crashed: {}", id, message);
+                            metrics.crashes.fetch_add(1, Ordering::Relaxed);
+                            *last_crash.lock().unwrap() = Some(message.clone());
+
+                            // a fresh server replaces the crashed one, with the same info
+                            let restarted = match restart {
+                                true => catch_panic(|| constructor.construct(&settings).map(|mut g| {
+                                    let config = g.init();
+                                    (g, config)
+                                })).and_then(|res| res),
+                                false => Err(message.clone())
+                            };
+                            {
+                                let mut crashes = crashes.lock().unwrap();
+                                if crashes.len() >= MAX_CRASHES {
+                                    crashes.pop_front();
+                                }
+                                crashes.push_back(AdminCrash {
+                                    instance:id,
+                                    message,
+                                    restarted:restarted.is_ok()
+                                });
+                            }
+
+                            match restarted {
+                                Ok((restarted_g, restarted_config)) => {
+                                    info!("Instance {} restarted", id);
+                                    restarts += 1;
+                                    g = restarted_g;
+                                    config = restarted_config;
+
+                                    // the clients of the crashed server are sent back to the lobby
+                                    let instance = {
+                                        let mut info = info.write().await;
+                                        info.current_players = 0;
+                                        info.max_players = config.max_players;
+                                        info.clone()
+                                    };
+                                    names.clear();
+                                    disconnected.clear();
+                                    held_seats.lock().unwrap().clear();
+                                    return_clients(&mut clients, &instance, "instance crashed and was restarted").await;
+                                    lobby_changed.send_replace(());
+
+                                    context.in_messages.clear();
+                                    context.out_messages.clear();
+                                    context.time = 0.0;
+                                    context.tick = 0;
+                                    context.tick_period = clamp_tick_period(config.tick_period);
+                                    context.ended = None;
+                                    continue;
+                                },
+                                Err(_) => {
+                                    info.write().await.crashed = true;
+                                    crashed.store(true, Ordering::Relaxed);
+                                    break "instance crashed".into();
+                                }
+                            }
+                        }
+
+                        if let Some(reason) = context.ended.take() {
+                            break reason;
+                        }
+
+                        if let Some(dir) = &snapshot_dir {
+                            if last_tick - last_snapshot >= snapshot_interval {
+                                last_snapshot = last_tick;
+                                take_snapshot(g.as_mut(), &*info.read().await, dir).await;
+                            }
+                        }
+
+                        if !clients.is_empty() {
+                            empty_since = last_tick;
+                        } else if let Some(timeout) = empty_timeout {
+                            if last_tick - empty_since >= timeout {
+                                break "instance was empty for too long".into();
+                            }
+                        }
                     },
                     msg = recv => {
                         match msg {
                             Some(msg) => {
                                 match msg {
                                     Msg::InstanceMsg(msg) => {
-                                        match &msg {
-                                            InMsg::ClientLeft { client_id } => {
-                                                if let Some((tx, transfer)) = clients.remove(client_id) {
-                                                    let mut host_info = info.write().await;
-                                                    host_info.current_players -= 1;
-                                                    let _ = transfer.send(tx);
-                                                }
-                                            },
-                                            _=>{}
+                                        if let InMsg::ClientLeft { client_id } = &msg {
+                                            names.remove(client_id);
+                                            if let Some((tx, transfer)) = clients.remove(client_id) {
+                                                let mut host_info = info.write().await;
+                                                host_info.current_players -= 1;
+                                                lobby_changed.send_replace(());
+                                                let _ = transfer.send(tx);
+                                            }
                                         }
     
                                         context.in_messages.push_back(msg);
                                     },
+                                    Msg::ClientDisconnected { client_id, held } => {
+                                        if clients.contains_key(&client_id) {
+                                            // hold the seat for a while
+                                            disconnected.insert(client_id, Instant::now() + reconnect_grace);
+                                            held_seats.lock().unwrap().insert(client_id);
+                                            context.in_messages.push_back(InMsg::ClientDisconnected {
+                                                client_id
+                                            });
+                                        }
+                                        let _ = held.send(());
+                                    },
                                     Msg::ClientTransfer { 
                                         client_id, 
                                         client_name,
                                         sink: mut tx, 
-                                        return_sink: return_tx 
+                                        return_sink: return_tx,
+                                        payload,
+                                        resume
                                     } => {
                                         let mut host_info = info.write().await;
-                                        if host_info.current_players >= host_info.max_players {
-                                            // if max players reach, reject.
-                                            let _ = tx.send(ServerMsg::JoinRejected {
+                                        if resume && disconnected.remove(&client_id).is_some() {
+                                            // put the client back into its held seat
+                                            held_seats.lock().unwrap().remove(&client_id);
+                                            context.in_messages.push_back(InMsg::ClientReconnected {
+                                                client_id
+                                            });
+                                            let _ = tx.send(ServerMsg::JoinedInstance {
                                                 instance:host_info.clone()
                                             }).await;
 
+                                            clients.insert(client_id, (tx, return_tx));
+                                        } else if let Err(reason) = admit(g.as_mut(), &host_info, &clients, client_id, &client_name, &payload) {
+                                            // if max players reach, the client is already in the instance or the server refuses, reject.
+                                            metrics.join_rejections.fetch_add(1, Ordering::Relaxed);
+                                            let _ = tx.send(ServerMsg::JoinRejected {
+                                                instance:host_info.clone(),
+                                                reason
+                                            }).await;
+
                                             let _ = return_tx.send(tx);
                                         } else {
                                             // else accept the join
+                                            names.insert(client_id, client_name.clone());
                                             context.in_messages.push_back(InMsg::ClientJoined {
-                                                client_id:client_id,
-                                                client_name:client_name
+                                                client_id,
+                                                client_name
                                             });
                                             host_info.current_players += 1;
+                                            lobby_changed.send_replace(());
                                             let _ = tx.send(ServerMsg::JoinedInstance {
                                                 instance:host_info.clone()
                                             }).await;
@@ -147,85 +449,371 @@ impl Instance {
                                         if let Some((tx, _)) = clients.get_mut(&client_id) {
                                             let server_bytes_sec = tx.bytes_per_second.per_second();
                                             let _ = tx.send(ServerMsg::Pong {
-                                                tick:tick,
-                                                server_bytes_sec:server_bytes_sec,
+                                                tick,
+                                                server_bytes_sec,
                                                 client_bytes_sec:server_bytes_sec
                                             }).await;
                                         }
+                                    },
+                                    Msg::End { reason, suspend } => {
+                                        suspended = suspend;
+                                        break reason;
+                                    },
+                                    Msg::Players { reply } => {
+                                        let players = names.iter().map(|(client_id, client_name)| Player {
+                                            client_id:*client_id,
+                                            client_name:client_name.clone(),
+                                            connected:!disconnected.contains_key(client_id)
+                                        }).collect();
+                                        let _ = reply.send(players);
+                                    },
+                                    Msg::Announce { message } => {
+                                        for (sink, _) in clients.values_mut() {
+                                            let _ = sink.send(ServerMsg::Announcement {
+                                                message:message.clone()
+                                            }).await;
+                                        }
                                     }
                                 }
                             },
-                            None => {}
+                            None => {
+                                break "instance was dropped".into();
+                            }
                         }
                     }
                 };
+            };
+
+            // let the server say its goodbyes, unless it crashed
+            if !crashed.load(Ordering::Relaxed) {
+                if let Err(message) = catch_panic(|| g.shutdown(&mut context)) {
+                    error!("Server panicked during shutdown: {}", message);
+                }
+                send_out_messages(&mut context, &mut clients).await;
+            }
+
+            // an ended instance is not restored, unless suspended
+            if let Some(dir) = &snapshot_dir {
+                let info = info.read().await.clone();
+                match suspended && !crashed.load(Ordering::Relaxed) {
+                    true => take_snapshot(g.as_mut(), &info, dir).await,
+                    false => snapshot::remove(dir, info.id).await
+                }
             }
+
+            // send remaining clients back to the lobby
+            let instance = info.read().await.clone();
+            drop(receiver);
+            lobby_changed.send_replace(());
+            info!("Instance {} ended: {}", instance.id, reason);
+            return_clients(&mut clients, &instance, &reason).await;
         });
 
-        return instance;
+        Ok(instance)
+    }
+
+    /// joins the client to the instance, passing `payload` to `Server::can_join`
+    pub async fn join(&self, client:Client, payload:Vec<u8>) -> Option<Client> {
+        self.enter(client, payload, false).await
+    }
+
+    /// puts the client back into its held seat, or joins it as usual if the seat is no longer held
+    pub async fn resume(&self, client:Client) -> Option<Client> {
+        self.enter(client, Vec::new(), true).await
     }
 
-    pub async fn join(&self, client:Client) -> Option<Client> {
+    /// returns true if the instance holds a seat for the disconnected client `client_id`
+    pub fn holds_seat(&self, client_id:Uuid) -> bool {
+        self.held_seats.lock().unwrap().contains(&client_id)
+    }
+
+    async fn enter(&self, client:Client, payload:Vec<u8>, resume:bool) -> Option<Client> {
         info!("Client {} with name '{}' joined Host {}", client.client_id, client.client_name, self.info.read().await.id);
         let tx = client.sink;
         let mut rx = client.stream;
 
-        let (return_tx, return_rx) = tokio::sync::oneshot::channel::<ClientSink>();
+        let (return_tx, mut return_rx) = tokio::sync::oneshot::channel::<ClientSink>();
         let host_sender = self.sender.clone();
         let _ = host_sender.send(Msg::ClientTransfer {
             client_id: client.client_id,
             client_name:client.client_name.clone(),
             sink: tx,
             return_sink: return_tx,
+            payload,
+            resume
         }).await;
 
-        while let Some(msg) = rx.next::<ClientMsg>().await {
-            match msg {
-                Ok(msg) => {
+        let mut farewell = None;
+        let mut dropped = false;
+        loop {
+            select! {
+                msg = client.session.disconnected() => {
+                    // a session taken over by a resuming connection keeps its seat
+                    dropped = client.session.is_taken_over();
+                    farewell = Some(msg);
+                    break;
+                },
+                sink = &mut return_rx => {
+                    // the instance handed back the sink on its own,
+                    // e.g. the join was rejected or the instance ended
+                    if let Ok(tx) = sink {
+                        return Some(Client {
+                            sink: tx,
+                            stream: rx,
+                            client_id:client.client_id,
+                            client_name:client.client_name,
+                            session:client.session
+                        });
+                    }
+
+                    // the instance dropped the sink, disconnecting a kicked client,
+                    // which must not resume its session
+                    client.session.revoke();
+                    return None;
+                },
+                msg = rx.next::<ClientMsg>() => {
                     match msg {
-                        ClientMsg::LeaveInstance {} => {
-                            // exit while and leave host
-                            break;
+                        Some(Ok(msg)) => {
+                            match msg {
+                                ClientMsg::LeaveInstance {} => {
+                                    // exit loop and leave host
+                                    break;
+                                },
+                                ClientMsg::CustomMsg {
+                                    msg
+                                } => {
+                                    let _ = host_sender.send(Msg::InstanceMsg(InMsg::CustomMsg {
+                                        client_id:client.client_id,
+                                        msg
+                                    })).await;
+                                },
+                                ClientMsg::Ping {
+                                    tick
+                                } => {
+                                    let _ = host_sender.send(Msg::Ping {
+                                        client_id:client.client_id,
+                                        tick
+                                    }).await;
+                                }
+                                _ => {}
+                            }
                         },
-                        ClientMsg::CustomMsg {
-                            msg
-                        } => {
-                            let _ = host_sender.send(Msg::InstanceMsg(InMsg::CustomMsg {
-                                client_id:client.client_id,
-                                msg
-                            })).await;
+                        _ => {
+                            dropped = true;
+                            break;
                         },
-                        ClientMsg::Ping {
-                            tick
-                        } => {
-                            let _ = host_sender.send(Msg::Ping {
-                                client_id:client.client_id,
-                                tick:tick
-                            }).await;
-                        }
-                        _ => {}
                     }
-                },
-                Err(_) => {
-                    break;
-                },
+                }
             }
         }
 
+        if dropped && self.reconnect_grace.is_some() {
+            // the connection was lost, let the instance hold the seat. wait for it,
+            // such that a client resuming right away finds the seat
+            let (held, seat_held) = tokio::sync::oneshot::channel();
+            let _ = host_sender.send(Msg::ClientDisconnected {
+                client_id:client.client_id,
+                held
+            }).await;
+            let _ = seat_held.await;
+            info!("Client {} disconnected from Host {}", client.client_id, self.info.read().await.id);
+            return None;
+        }
+
         let _ = host_sender.send(Msg::InstanceMsg(InMsg::ClientLeft {
             client_id:client.client_id
         })).await;
         
         info!("Client {} left Host {}", client.client_id, self.info.read().await.id);
-        if let Ok(tx) = return_rx.await {
+        if let Ok(mut tx) = return_rx.await {
+            if let Some(farewell) = farewell {
+                let _ = tx.send(farewell).await;
+                return None;
+            }
+
             return Some(Client {
                 sink: tx,
                 stream: rx,
                 client_id:client.client_id,
-                client_name:client.client_name
+                client_name:client.client_name,
+                session:client.session
             });
         };
 
+        // kicked with a disconnect while leaving
+        client.session.revoke();
         None
     }
-}
\ No newline at end of file
+
+    /// ends the instance, sending all clients back to the lobby with `ServerMsg::InstanceEnded`
+    pub async fn end(&self, reason:&str) {
+        let _ = self.sender.send(Msg::End {
+            reason:reason.into(),
+            suspend:false
+        }).await;
+    }
+
+    /// ends the instance like `end`, but takes a final snapshot which is restored
+    /// when the master starts again. same as `end` if snapshots are disabled
+    pub async fn suspend(&self, reason:&str) {
+        let _ = self.sender.send(Msg::End {
+            reason:reason.into(),
+            suspend:true
+        }).await;
+    }
+
+    /// returns true if the instance task has ended
+    pub fn is_ended(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// returns true if the instance ended because its server crashed
+    pub fn is_crashed(&self) -> bool {
+        self.crashed.load(Ordering::Relaxed)
+    }
+
+    /// returns the clients seated in the instance
+    pub async fn players(&self) -> Vec<Player> {
+        let (reply, players) = tokio::sync::oneshot::channel();
+        let _ = self.sender.send(Msg::Players {
+            reply
+        }).await;
+        players.await.unwrap_or_default()
+    }
+
+    /// sends `ServerMsg::Announcement` with `message` to every client in the instance
+    pub async fn announce(&self, message:&str) {
+        let _ = self.sender.send(Msg::Announce {
+            message:message.into()
+        }).await;
+    }
+
+    /// returns the panic message of the last crash of the server, if it crashed
+    pub fn last_crash(&self) -> Option<String> {
+        self.last_crash.lock().unwrap().clone()
+    }
+
+    /// waits until the instance task has ended
+    pub async fn ended(&self) {
+        self.sender.closed().await
+    }
+}
+
+/// the number of crashes kept for the admin API
+const MAX_CRASHES:usize = 100;
+
+/// runs `f`, returning the panic message if it panics
+fn catch_panic<T>(f:impl FnOnce() -> T) -> Result<T, String> {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            return message.to_string();
+        }
+
+        if let Some(message) = payload.downcast_ref::<String>() {
+            return message.clone();
+        }
+
+        "unknown panic".into()
+    })
+}
+
+/// sends all clients back to the lobby with `ServerMsg::InstanceEnded`
+async fn return_clients(clients:&mut HashMap<Uuid, (ClientSink, tokio::sync::oneshot::Sender<ClientSink>)>, instance:&InstanceInfo, reason:&str) {
+    for (_, (mut sink, return_sink)) in clients.drain() {
+        let _ = sink.send(ServerMsg::InstanceEnded {
+            instance:instance.clone(),
+            reason:reason.into()
+        }).await;
+        let _ = return_sink.send(sink);
+    }
+}
+
+/// stores the state of the server in `dir`, if the server supports snapshots
+async fn take_snapshot(g:&mut dyn server::Server, info:&InstanceInfo, dir:&Path) {
+    let state = match catch_panic(|| g.snapshot()) {
+        Ok(state) => state,
+        Err(message) => {
+            error!("Server of instance {} panicked during snapshot: {}", info.id, message);
+            None
+        }
+    };
+    if let Some(state) = state {
+        let snapshot = Snapshot {
+            info:info.clone(),
+            state
+        };
+        if let Err(err) = snapshot::save(dir, &snapshot).await {
+            error!("Could not snapshot instance {}: {}", info.id, err);
+        }
+    }
+}
+
+/// decides if a client can join, returning the reason if it cannot
+fn admit(g:&mut dyn server::Server, info:&InstanceInfo, clients:&HashMap<Uuid, (ClientSink, tokio::sync::oneshot::Sender<ClientSink>)>, client_id:Uuid, client_name:&str, payload:&[u8]) -> Result<(), String> {
+    if clients.contains_key(&client_id) {
+        return Err("already in the instance".into());
+    }
+
+    if info.current_players >= info.max_players {
+        return Err("instance is full".into());
+    }
+
+    catch_panic(|| g.can_join(client_id, client_name, payload)).unwrap_or_else(|message| {
+        error!("Server panicked in can_join: {}", message);
+        Err("server error".into())
+    })
+}
+
+/// sends the messages pushed by the server to the clients
+///
+/// returns the clients which were kicked and removed from `clients`
+async fn send_out_messages(context:&mut Ctx, clients:&mut HashMap<Uuid, (ClientSink, tokio::sync::oneshot::Sender<ClientSink>)>) -> Vec<Uuid> {
+    let mut kicked = Vec::new();
+    for msg in context.out_messages.drain(..) {
+        match msg {
+            server::OutMsg::CustomToAll { msg } => {
+                for (sink, _) in &mut clients.values_mut() {
+                    let _ = sink.send(ServerMsg::Custom{
+                        msg:msg.clone()
+                    }).await;
+                }
+            },
+            server::OutMsg::CustomTo { client_id, msg } => {
+                if let Some((sink, _)) = clients.get_mut(&client_id) {
+                    let _ = sink.send(ServerMsg::Custom{
+                        msg:msg.clone()
+                    }).await;
+                }
+            },
+            server::OutMsg::UnreliableToAll { msg } => {
+                for (sink, _) in &mut clients.values_mut() {
+                    let _ = sink.send_unreliable(&msg).await;
+                }
+            },
+            server::OutMsg::UnreliableTo { client_id, msg } => {
+                if let Some((sink, _)) = clients.get_mut(&client_id) {
+                    let _ = sink.send_unreliable(&msg).await;
+                }
+            },
+            server::OutMsg::Kick { client_id, reason, disconnect } => {
+                if let Some((mut sink, return_sink)) = clients.remove(&client_id) {
+                    info!("Client {} kicked: {}", client_id, reason);
+                    let _ = sink.send(ServerMsg::Kicked {
+                        reason
+                    }).await;
+                    if disconnect {
+                        // dropping the return sink ends the connection of the client
+                        // and revokes its resume token
+                        let _ = sink.close().await;
+                    } else {
+                        let _ = return_sink.send(sink);
+                    }
+
+                    kicked.push(client_id);
+                }
+            }
+        }
+    }
+
+    kicked
+}
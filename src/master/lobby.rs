@@ -1,52 +1,157 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::{HashMap, VecDeque}, sync::{Arc, Mutex}};
 
 use log::info;
-use tokio::sync::RwLock;
+use tokio::sync::watch;
 use uuid::Uuid;
-use crate::{server::{Constructor}};
+use super::{AdminCrash, Config, Metrics, snapshot::Snapshot};
 
 use super::instance::Instance;
 use crate::shared::InstanceInfo;
 
 pub struct Lobby {
-    instances:HashMap<Uuid, Instance>
+    instances:HashMap<Uuid, Instance>,
+    metrics:Arc<Metrics>,
+
+    /// marked whenever an instance is added, removed or its info changes
+    changed:Arc<watch::Sender<()>>,
+
+    /// the most recent crashes of servers
+    crashes:Arc<Mutex<VecDeque<AdminCrash>>>
 }
 
 impl Lobby {
-    pub fn new() -> Self {
+    pub fn new(metrics:Arc<Metrics>) -> Self {
         Lobby {
-            instances:HashMap::new()
+            instances:HashMap::new(),
+            metrics,
+            changed:Arc::new(watch::channel(()).0),
+            crashes:Arc::new(Mutex::new(VecDeque::new()))
         }
     }
 
-    pub fn new_instance(&mut self, creator:Uuid, constructor:Constructor) -> Uuid {
+    /// returns the most recent crashes of servers, oldest first
+    pub fn crashes(&self) -> Vec<AdminCrash> {
+        self.crashes.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// returns a receiver which is notified whenever the list of instances changes
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.changed.subscribe()
+    }
+
+    /// creates a new instance of `kind` with `settings` using the constructor registered in `config`
+    ///
+    /// returns the reason if the `kind` is unknown or the constructor rejects the settings
+    pub fn new_instance(&mut self, creator:Uuid, kind:&str, settings:&[u8], config:&Config) -> Result<Uuid, String> {
+        let constructor = config.constructors.get(kind).ok_or(format!("unknown kind '{}'", kind))?.clone();
+
+        // forget about instances which has ended on their own, crashed ones stay listed until removed
+        self.instances.retain(|_, instance| !instance.is_ended() || instance.is_crashed());
+
         let id = Uuid::new_v4();
-        let instance = Instance::new(Arc::new(RwLock::new(InstanceInfo {
+        let instance = Instance::new(InstanceInfo {
             id,
-            creator:creator,
+            creator,
+            kind:kind.into(),
             max_players:0,
-            current_players:0
-        })), constructor);
+            current_players:0,
+            settings:settings.into(),
+            crashed:false
+        }, constructor, None, config, self.metrics.clone(), self.changed.clone(), self.crashes.clone())?;
 
         self.instances.insert(id, instance);
-        info!("Host {:?} created by client {}", id, creator);
-        return id;
+        self.changed.send_replace(());
+        info!("Host {:?} of kind '{}' created by client {}", id, kind, creator);
+        Ok(id)
     }
 
+    /// recreates an instance from a snapshot, keeping its id, kind and creator
+    pub fn restore_instance(&mut self, snapshot:Snapshot, config:&Config) -> Result<Uuid, String> {
+        let info = snapshot.info;
+        let constructor = config.constructors.get(&info.kind).ok_or(format!("unknown kind '{}'", info.kind))?.clone();
+        if self.instances.contains_key(&info.id) {
+            return Err("instance already exists".into());
+        }
+
+        let id = info.id;
+        let kind = info.kind.clone();
+        let instance = Instance::new(info, constructor, Some(&snapshot.state), config, self.metrics.clone(), self.changed.clone(), self.crashes.clone())?;
+        self.instances.insert(id, instance);
+        self.changed.send_replace(());
+        info!("Host {:?} of kind '{}' restored from snapshot", id, kind);
+        Ok(id)
+    }
+
+    /// returns the info of the instances shown to clients, including crashed ones
     pub async fn instances(&self) -> Vec<InstanceInfo> {
         let mut list = Vec::new();
-        for (_, host) in self.instances.iter() {
+        for host in self.listed_instances() {
             list.push(host.info.read().await.clone());
         }
        
-        return list;
+        list
+    }
+
+    /// returns the number of instances created by `creator`
+    pub async fn instances_created_by(&self, creator:Uuid) -> u32 {
+        let mut count = 0;
+        for host in self.live_instances() {
+            if host.info.read().await.creator == creator {
+                count += 1;
+            }
+        }
+
+        count
     }
 
     pub fn get_instance(&self, id:Uuid) -> Option<Instance> {
         if let Some(host) = self.instances.get(&id) {
-            return Some(host.clone());
+            if !host.is_ended() {
+                return Some(host.clone());
+            }
         }
 
         None
     }
+
+    /// returns the info of the instance `id` if it crashed, such that joining it can be rejected
+    pub async fn crashed_instance(&self, id:Uuid) -> Option<InstanceInfo> {
+        match self.instances.get(&id) {
+            Some(host) if host.is_crashed() => Some(host.info.read().await.clone()),
+            _ => None
+        }
+    }
+
+    /// returns the instance holding a seat for the disconnected client `client_id`
+    pub fn find_held_seat(&self, client_id:Uuid) -> Option<Instance> {
+        self.live_instances().find(|instance| instance.holds_seat(client_id)).cloned()
+    }
+
+    /// removes the instance from the lobby, returning it such that it can be ended
+    pub fn remove_instance(&mut self, id:Uuid) -> Option<Instance> {
+        let instance = self.instances.remove(&id);
+        self.changed.send_replace(());
+        instance
+    }
+
+    /// returns all instances which have not ended, and those which crashed
+    pub fn all_instances(&self) -> Vec<Instance> {
+        self.listed_instances().cloned().collect()
+    }
+
+    /// removes all instances from the lobby, returning them such that they can be ended
+    pub fn remove_all(&mut self) -> Vec<Instance> {
+        let instances = self.instances.drain().map(|(_, instance)| instance).collect();
+        self.changed.send_replace(());
+        instances
+    }
+
+    fn live_instances(&self) -> impl Iterator<Item = &Instance> {
+        self.instances.values().filter(|instance| !instance.is_ended())
+    }
+
+    /// crashed instances are listed, marked as crashed, until they are removed
+    fn listed_instances(&self) -> impl Iterator<Item = &Instance> {
+        self.instances.values().filter(|instance| !instance.is_ended() || instance.is_crashed())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+use std::{fmt::Write, sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use crate::shared::InstanceInfo;
+
+/// counters exported in Prometheus text format on `/metrics`
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub messages_in:AtomicU64,
+    pub messages_out:AtomicU64,
+    pub bytes_in:AtomicU64,
+    pub bytes_out:AtomicU64,
+    pub join_rejections:AtomicU64,
+    pub ticks:AtomicU64,
+    pub tick_nanos:AtomicU64,
+    pub tick_overruns:AtomicU64,
+    pub crashes:AtomicU64
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn message_in(&self, bytes:usize) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn message_out(&self, bytes:usize) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// records a tick which took `took` while being scheduled every `period`
+    pub fn tick(&self, took:Duration, period:Duration) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.tick_nanos.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+        if took > period {
+            self.tick_overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// renders the metrics in Prometheus text format
+    ///
+    /// `instances` are the running instances with the number of connected clients in each
+    pub fn render(&self, connected_clients:usize, instances:&[(InstanceInfo, usize)]) -> String {
+        let clients_in_instances:usize = instances.iter().map(|(_, connected)| connected).sum();
+        let clients_in_lobby = connected_clients.saturating_sub(clients_in_instances);
+        let mut out = String::new();
+        let mut metric = |name:&str, kind:&str, help:&str, value:String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        metric("hostess_connected_clients", "gauge", "Clients with an authenticated session.", connected_clients.to_string());
+        metric("hostess_clients_in_lobby", "gauge", "Clients currently in the lobby.", clients_in_lobby.to_string());
+        metric("hostess_clients_in_instances", "gauge", "Clients currently in an instance.", clients_in_instances.to_string());
+        metric("hostess_instances", "gauge", "Instances currently running.", instances.len().to_string());
+        metric("hostess_messages_in_total", "counter", "Messages received from clients.", self.messages_in.load(Ordering::Relaxed).to_string());
+        metric("hostess_messages_out_total", "counter", "Messages sent to clients.", self.messages_out.load(Ordering::Relaxed).to_string());
+        metric("hostess_bytes_in_total", "counter", "Bytes received from clients on the application level.", self.bytes_in.load(Ordering::Relaxed).to_string());
+        metric("hostess_bytes_out_total", "counter", "Bytes sent to clients on the application level.", self.bytes_out.load(Ordering::Relaxed).to_string());
+        metric("hostess_join_rejections_total", "counter", "Attempts to join an instance which were rejected.", self.join_rejections.load(Ordering::Relaxed).to_string());
+        metric("hostess_tick_overruns_total", "counter", "Ticks which took longer than the tick period.", self.tick_overruns.load(Ordering::Relaxed).to_string());
+        metric("hostess_instance_crashes_total", "counter", "Panics of servers in an instance.", self.crashes.load(Ordering::Relaxed).to_string());
+
+        let _ = writeln!(out, "# HELP hostess_tick_duration_seconds Time spent in Server::tick.");
+        let _ = writeln!(out, "# TYPE hostess_tick_duration_seconds summary");
+        let _ = writeln!(out, "hostess_tick_duration_seconds_sum {}", Duration::from_nanos(self.tick_nanos.load(Ordering::Relaxed)).as_secs_f64());
+        let _ = writeln!(out, "hostess_tick_duration_seconds_count {}", self.ticks.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP hostess_instance_players Players seated in an instance.");
+        let _ = writeln!(out, "# TYPE hostess_instance_players gauge");
+        for (instance, _) in instances {
+            let _ = writeln!(out, "hostess_instance_players{{instance=\"{}\",kind=\"{}\"}} {}", instance.id, escape(&instance.kind), instance.current_players);
+        }
+
+        out
+    }
+}
+
+/// escapes a label value
+fn escape(value:&str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -3,23 +3,139 @@ use lobby::*;
 
 mod instance;
 
-use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Instant};
+mod auth;
+pub use auth::*;
 
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-     SinkExt, StreamExt,
-};
+mod session;
+pub use session::*;
+
+mod shutdown;
+pub use shutdown::*;
+
+mod admin;
+pub use admin::{AdminCrash, AdminInstance, AdminPlayer};
+
+mod metrics;
+pub use metrics::*;
+
+mod snapshot;
+
+mod execution;
+pub use execution::*;
+
+mod tls;
+pub use tls::Tls;
+
+mod http;
+pub use http::{Http, Routes, StaticFiles};
+
+mod transport;
+pub use transport::{FrameSink, FrameStream};
+
+mod udp;
+pub use udp::MAX_DATAGRAM_LEN;
+use udp::{Udp, UdpRoute};
+
+use std::{collections::HashMap, io::{Error, ErrorKind}, net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc, time::{Duration, Instant}};
+
+use futures_util::{SinkExt, StreamExt};
 use log::{error, info};
-use tokio::{sync::RwLock, task::JoinHandle};
+use tokio::{select, sync::RwLock, task::JoinHandle};
 use uuid::Uuid;
-use warp::{Error, Filter, ws::{Message, WebSocket}};
 
-use crate::{bincoded::Bincoded, client::{ClientMsg, ServerMsg}, server::{Constructor}};
+use crate::{bincoded::Bincoded, client::{ClientMsg, ServerMsg, UdpMsg}, compression::{Codec, Compression, Framing}, server::{Constructor}, shared::InstanceInfo};
+
+/// the kind under which the constructor given to `Master::new` is registered
+pub const DEFAULT_KIND:&str = "default";
 
 #[derive(Clone)]
 pub struct Config {
+    /// allows clients to create instances using `ClientMsg::CreateInstance`
     pub host_creation:bool,
-    pub constructor:Constructor
+
+    /// max number of instances a single creator can have at the same time.
+    /// `0` means unlimited
+    pub max_instances_per_creator:u32,
+
+    /// destroys instances which have been without players for the given duration.
+    /// `None` keeps empty instances around forever
+    pub empty_instance_timeout:Option<Duration>,
+
+    /// constructors of the game servers which can be hosted, by kind
+    pub constructors:HashMap<String, Constructor>,
+
+    /// the kind used when no kind is specified
+    pub default_kind:String,
+
+    /// decides which clients can join the lobby and with what identity
+    pub authenticator:Arc<dyn Authenticator>,
+
+    /// what to do when an identity connects while already having a session
+    pub duplicate_session:DuplicateSession,
+
+    /// how long the seat of a disconnected client is held in its instance,
+    /// allowing it to reconnect with its resume token. `None` removes the client immediately
+    pub reconnect_grace:Option<Duration>,
+
+    /// token required by the JSON admin API below `/admin`. `None` disables the admin API
+    pub admin_token:Option<String>,
+
+    /// serves Prometheus metrics on `/metrics`. disabled by default, as anyone reaching the
+    /// game port could read them. expose them only on a trusted network
+    pub metrics:bool,
+
+    /// min time between two pushes of `InstanceAdded`, `InstanceUpdated` and `InstanceRemoved`
+    /// to a client in the lobby, changes in between are batched. `None` disables the pushes,
+    /// leaving clients to poll using `ClientMsg::RefreshInstances`
+    pub lobby_update_interval:Option<Duration>,
+
+    /// directory in which snapshots of the instances are stored, see `Server::snapshot`.
+    /// instances found in the directory are restored by `start()` with their previous id.
+    /// `None` disables snapshots
+    pub snapshot_dir:Option<PathBuf>,
+
+    /// how often instances are snapshotted if `snapshot_dir` is set.
+    /// a final snapshot is taken when the master shuts down
+    pub snapshot_interval:Duration,
+
+    /// what to do when the server of an instance panics
+    pub restart_policy:RestartPolicy,
+
+    /// where instances run their servers, e.g. on dedicated threads for CPU heavy servers
+    pub execution:Execution,
+
+    /// serves the WebSocket and static files over TLS instead of plain TCP. `None` serves plain TCP
+    pub tls:Option<Tls>,
+
+    /// the WebSocket path, static files and allowed origins served by `start()` and `routes()`
+    pub http:Http,
+
+    /// address on which raw TCP clients are accepted, e.g. `0.0.0.0:1235`. `None` only accepts WebSocket clients.
+    /// messages are framed with their length as big endian u32, see `client::tcp_client`
+    pub tcp_addr:Option<String>,
+
+    /// address of the UDP side channel for unreliable messages, e.g. `0.0.0.0:1236`, see `ServerMsg::UdpAvailable`.
+    /// `None` sends unreliable messages over the connection of the client
+    pub udp_addr:Option<String>,
+
+    /// messages of at least this many bytes are compressed with LZ4 for clients offering it in `Hello`,
+    /// likewise the clients compress their messages. `None` disables compression
+    pub compression_threshold:Option<u32>
+}
+
+/// what to do when the server of an instance panics.
+/// in any case its clients are sent back to the lobby with `ServerMsg::InstanceEnded`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum RestartPolicy {
+    /// end the instance, marking it as crashed in its `InstanceInfo`
+    #[default]
+    Never,
+
+    /// replace the server with a freshly constructed one, keeping the instance and its id.
+    /// the instance ends once it has been restarted `max_restarts` times
+    Limited {
+        max_restarts:u32
+    }
 }
 
 /// takes care of hosting one or more servers
@@ -28,6 +144,10 @@ pub struct Config {
 pub struct Master {
     addr: String,
     lobby: Arc<RwLock<Lobby>>,
+    sessions: Arc<Sessions>,
+    shutdown: Shutdown,
+    metrics: Arc<Metrics>,
+    udp: Arc<Udp>,
     config:Config
 }
 
@@ -35,169 +155,442 @@ pub struct Client {
     pub sink: ClientSink,
     pub stream: ClientStream,
     pub client_id:Uuid,
-    pub client_name:String
+    pub client_name:String,
+    pub(crate) session:Arc<Session>
 }
 
+/// sends messages to a client, regardless of its transport
 pub struct ClientSink {
-    sink:SplitSink<WebSocket, Message>,
-    pub bytes_per_second:Measurement
+    sink:FrameSink,
+    pub bytes_per_second:Measurement,
+    metrics:Arc<Metrics>,
+    udp:Option<UdpRoute>,
+    framing:Framing
 }
 
 impl ClientSink {
+    pub fn new(sink:FrameSink, metrics:Arc<Metrics>) -> Self {
+        Self {
+            sink,
+            bytes_per_second:Measurement::new(),
+            metrics,
+            udp:None,
+            framing:Framing::default()
+        }
+    }
+
     pub async fn send(&mut self, msg:ServerMsg) -> Result<(), Error> {
         let msg = msg.to_bincode();
-        self.bytes_per_second.sample(msg.len() as f32);
-        self.sink.send(Message::binary(msg)).await
+        let uncompressed = msg.len();
+        let msg = self.framing.encode(msg);
+        self.bytes_per_second.sample_compressed(msg.len() as f32, uncompressed as f32);
+        self.metrics.message_out(msg.len());
+        self.sink.send(msg).await
     }
-}
 
-impl From<SplitSink<WebSocket, Message>> for ClientSink {
-    fn from(sink: SplitSink<WebSocket, Message>) -> Self {
-        Self {
-            sink,
-            bytes_per_second:Measurement::new()
+    /// sends a custom message over the UDP side channel of the client, falling back to
+    /// `ServerMsg::Custom` if the client has none or the message is too large for a datagram
+    pub async fn send_unreliable(&mut self, msg:&[u8]) -> Result<(), Error> {
+        if let Some(udp) = &self.udp {
+            let datagram = UdpMsg::Custom { msg:msg.to_vec() }.to_bincode();
+            if datagram.len() <= MAX_DATAGRAM_LEN && udp.send(&datagram).await {
+                self.bytes_per_second.sample(datagram.len() as f32);
+                self.metrics.message_out(datagram.len());
+                return Ok(());
+            }
         }
+
+        self.send(ServerMsg::Custom { msg:msg.to_vec() }).await
+    }
+
+    /// closes the connection to the client
+    pub async fn close(&mut self) -> Result<(), Error> {
+        self.sink.close().await
     }
 }
+
+/// receives messages from a client, regardless of its transport
 pub struct ClientStream {
-    stream: SplitStream<WebSocket>,
-    pub bytes_per_second:Measurement
+    stream:FrameStream,
+    pub bytes_per_second:Measurement,
+    metrics:Arc<Metrics>,
+    framing:Framing
 }
 
+/// bytes transferred per second, both as sent over the transport and before compression
 pub struct Measurement {
     temp:f32,
     per_second:f32,
+    temp_uncompressed:f32,
+    uncompressed_per_second:f32,
     start_time:Instant
 }
 
 impl Measurement {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             temp:0.0,
             per_second:0.0, 
+            temp_uncompressed:0.0,
+            uncompressed_per_second:0.0,
             start_time:Instant::now()
         }
     }
 
+    /// records an uncompressed message of `value` bytes
     pub fn sample(&mut self, value:f32) {
+        self.sample_compressed(value, value);
+    }
+
+    /// records a message of `value` bytes, which were `uncompressed` bytes before compression
+    pub fn sample_compressed(&mut self, value:f32, uncompressed:f32) {
         self.per_second();
         self.temp += value;
+        self.temp_uncompressed += uncompressed;
     }
 
+    /// returns the bytes sent over the transport during the last second
     pub fn per_second(&mut self) -> f32 {
         let now = Instant::now();
         let diff = Instant::now() - self.start_time;
         if diff.as_secs_f32() > 1.0 {
             self.per_second = self.temp;
+            self.uncompressed_per_second = self.temp_uncompressed;
             self.temp = 0.0;
+            self.temp_uncompressed = 0.0;
             self.start_time = now;
         }
 
         self.per_second
     }
+
+    /// returns the bytes before compression during the last second
+    pub fn uncompressed_per_second(&mut self) -> f32 {
+        self.per_second();
+        self.uncompressed_per_second
+    }
 }
 
 impl ClientStream {
+    pub fn new(stream:FrameStream, metrics:Arc<Metrics>) -> Self {
+        Self {
+            stream,
+            bytes_per_second:Measurement::new(),
+            metrics,
+            framing:Framing::default()
+        }
+    }
+
+    /// receives the next raw message, decompressed and with its size recorded
+    pub async fn recv(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        let frame = match self.stream.next().await? {
+            Ok(frame) => frame,
+            Err(err) => return Some(Err(err))
+        };
+        let len = frame.len();
+        self.metrics.message_in(len);
+        let msg = match self.framing.decode(frame) {
+            Some(msg) => msg,
+            None => return Some(Err(Error::new(ErrorKind::InvalidData, "malformed compressed message")))
+        };
+        self.bytes_per_second.sample_compressed(len as f32, msg.len() as f32);
+
+        Some(Ok(msg))
+    }
+
+    #[allow(clippy::needless_lifetimes)]
     pub async fn next<'a, T : Bincoded>(&'a mut self) -> Option<Result<T, Box<dyn std::error::Error + Send>>> {
-        match self.stream.next().await {
+        match self.recv().await {
             Some(msg) => {
                 match msg {
                     Ok(msg) => {
-                        let bytes = msg.as_bytes();
-                        self.bytes_per_second.sample(bytes.len() as f32);
-                        match T::from_bincode(bytes) {
-                            Some(msg) => {
-                                return Some(Ok(msg));
-                            },
-                            None => {
-                                None 
-                            },
-                        }
-
+                        T::from_bincode(&msg).map(Ok)
                     },
                     Err(err) => {
-                        return Some(Err(Box::new(err)));
+                        Some(Err(Box::new(err)))
                     },
                 }
             },
             None => {
-                return None;
+                None
             },
         }
     }
 }
 
-impl From<SplitStream<WebSocket>> for ClientStream {
-    fn from(stream: SplitStream<WebSocket>) -> Self {
-        Self {
-            stream,
-            bytes_per_second:Measurement::new()
-        }
+/// sends the difference between the `known` instances and the current `instances` to a client in the lobby
+async fn send_instance_changes(sink:&mut ClientSink, known:&mut HashMap<Uuid, InstanceInfo>, instances:Vec<InstanceInfo>) -> Result<(), Error> {
+    let mut removed:Vec<Uuid> = known.keys().copied().collect();
+    for instance in instances {
+        removed.retain(|id| *id != instance.id);
+        let msg = match known.get(&instance.id) {
+            Some(info) if *info == instance => continue,
+            Some(_) => ServerMsg::InstanceUpdated { instance:instance.clone() },
+            None => ServerMsg::InstanceAdded { instance:instance.clone() }
+        };
+        known.insert(instance.id, instance);
+        sink.send(msg).await?;
+    }
+
+    for instance_id in removed {
+        known.remove(&instance_id);
+        sink.send(ServerMsg::InstanceRemoved { instance_id }).await?;
     }
+
+    Ok(())
 }
 
 impl Master {
     /// instantiates a new Hostess instance.
     /// `constructor` is the function responsible for constructing the Server on a new instace
+    /// and is registered as the `DEFAULT_KIND`
     pub fn new(addr: &str, constructor:Constructor) -> Self {
+        let mut constructors = HashMap::new();
+        constructors.insert(DEFAULT_KIND.into(), constructor);
+        let metrics = Arc::new(Metrics::new());
         Self {
             addr: addr.into(),
-            lobby: Arc::new(RwLock::new(Lobby::new())),
-            config:Config { host_creation: false, constructor:constructor }
+            lobby: Arc::new(RwLock::new(Lobby::new(metrics.clone()))),
+            sessions: Arc::new(Sessions::new()),
+            shutdown: Shutdown::new(),
+            metrics,
+            udp: Arc::new(Udp::default()),
+            config:Config {
+                host_creation: false,
+                max_instances_per_creator:1,
+                empty_instance_timeout:None,
+                constructors,
+                default_kind:DEFAULT_KIND.into(),
+                authenticator:Arc::new(AssignIds),
+                duplicate_session:DuplicateSession::KickOld,
+                reconnect_grace:None,
+                admin_token:None,
+                metrics:false,
+                lobby_update_interval:Some(Duration::from_millis(500)),
+                snapshot_dir:None,
+                snapshot_interval:Duration::from_secs(10),
+                restart_policy:RestartPolicy::Never,
+                execution:Execution::Runtime,
+                tls:None,
+                http:Http::new(),
+                tcp_addr:None,
+                udp_addr:None,
+                compression_threshold:Some(1024)
+            }
+        }
+    }
+
+    /// registers an additional kind of game server, which can be instantiated using `new_instance_of`
+    pub fn add_constructor(&mut self, kind:&str, constructor:Constructor) {
+        self.config.constructors.insert(kind.into(), constructor);
+    }
+
+    /// returns a handle which can be used to gracefully shut down the master once started
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// returns the config of the master, which can be changed before calling `start()`
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// creates a new server instance of the default kind with the given `creator` id
+    ///
+    /// returns the reason if no constructor is registered for the default kind or it requires settings
+    pub async fn new_instance(&mut self, creator:Uuid) -> Result<Uuid, String> {
+        let kind = self.config.default_kind.clone();
+        self.new_instance_with(&kind, creator, &[]).await
+    }
+
+    /// creates a new server instance of the given `kind` with the given `creator` id
+    /// 
+    /// returns the reason if no constructor is registered for `kind` or it requires settings
+    pub async fn new_instance_of(&mut self, kind:&str, creator:Uuid) -> Result<Uuid, String> {
+        self.new_instance_with(kind, creator, &[]).await
+    }
+
+    /// creates a new server instance of the given `kind` with the given `creator` id,
+    /// passing `settings` to the constructor
+    ///
+    /// returns the reason if no constructor is registered for `kind` or it rejects the settings
+    pub async fn new_instance_with(&mut self, kind:&str, creator:Uuid, settings:&[u8]) -> Result<Uuid, String> {
+        let mut lobby = self.lobby.write().await;
+        lobby.new_instance(creator, kind, settings, &self.config)
+    }
+
+    /// removes the instance with the given `id` from the lobby and ends it.
+    /// clients in the instance are sent back to the lobby with `ServerMsg::InstanceEnded`
+    ///
+    /// returns false if no such instance exists
+    pub async fn remove_instance(&mut self, id:Uuid) -> bool {
+        let instance = self.lobby.write().await.remove_instance(id);
+        match instance {
+            Some(instance) => {
+                instance.end("instance was removed").await;
+                true
+            },
+            None => false
         }
     }
 
-    /// creates a new server instance with the given `creator` id
-    pub async fn new_instance(&mut self, creator:Uuid) {
+    /// recreates the instances stored in `Config::snapshot_dir`
+    ///
+    /// returns the number of instances restored
+    async fn restore_instances(&self) -> usize {
+        let dir = match &self.config.snapshot_dir {
+            Some(dir) => dir.clone(),
+            None => return 0
+        };
+
+        let mut restored = 0;
         let mut lobby = self.lobby.write().await;
-        lobby.new_instance(creator, self.config.constructor.clone());
+        for snapshot in snapshot::load_all(&dir).await {
+            let id = snapshot.info.id;
+            match lobby.restore_instance(snapshot, &self.config) {
+                Ok(_) => restored += 1,
+                Err(reason) => error!("Could not restore instance {}: {}", id, reason)
+            }
+        }
+
+        restored
     }
 
     async fn client_joined_lobby(
         mut client:Client,
-        lobby: Arc<RwLock<Lobby>>,
-        _config:Config
+        master:Master
     ) {
         info!("Client {:?} entered lobby", client.client_id);
+        let lobby = &master.lobby;
+        let config = &master.config;
+
+        // put the client back into the instance holding its seat
+        let held = lobby.read().await.find_held_seat(client.client_id);
+        if let Some(host) = held {
+            match host.resume(client).await {
+                Some(c) => client = c,
+                None => return
+            }
+        }
+
+        // subscribe before sending the list, such that no change is missed
+        let mut changes = lobby.read().await.subscribe();
+        changes.borrow_and_update();
 
         // send list of hosts to client
+        let instances = lobby.read().await.instances().await;
+        let mut known:HashMap<Uuid, InstanceInfo> = instances.iter().map(|instance| (instance.id, instance.clone())).collect();
         let _ = client.sink.send(ServerMsg::Instances {
-            instances:lobby.read().await.instances().await
+            instances
         }).await;
 
-        while let Some(msg) = client.stream.stream.next().await {
+        // changes are pushed at most once every interval
+        let interval = config.lobby_update_interval;
+        let mut pending = false;
+        let mut next_update = tokio::time::Instant::now();
+
+        loop {
+            let msg = select! {
+                farewell = client.session.disconnected() => {
+                    let _ = client.sink.send(farewell).await;
+                    break;
+                },
+                res = changes.changed(), if interval.is_some() && !pending => {
+                    if res.is_ok() {
+                        pending = true;
+                    }
+                    continue;
+                },
+                _ = tokio::time::sleep_until(next_update), if pending => {
+                    pending = false;
+                    next_update = tokio::time::Instant::now() + interval.unwrap_or_default();
+                    let instances = lobby.read().await.instances().await;
+                    if send_instance_changes(&mut client.sink, &mut known, instances).await.is_err() {
+                        break;
+                    }
+                    continue;
+                },
+                msg = client.stream.recv() => msg
+            };
+            let msg = match msg {
+                Some(msg) => msg,
+                None => break
+            };
+
             match msg {
-                Ok(msg) => {
-                    let bytes = msg.as_bytes();
-                    if bytes.len() > 0 {
-                        match bincode::deserialize::<ClientMsg>(bytes) {
+                Ok(bytes) => {
+                    if !bytes.is_empty() {
+                        match bincode::deserialize::<ClientMsg>(&bytes) {
                             Ok(msg) => {
                                 match msg {
-                                 /*   ClientMsg::CreateHost {} => {
-                                        if config.host_creation {
-                                            // create new host
-                                            let mut lobby = lobby.write().await;
-                                            let host_id = lobby.new_host(client.client_id, config.constructor.clone());
-
-                                            // and tell this to the  client
-                                            let _ = client.sink.send(ServerMsg::HostCreated {
-                                                host_id:host_id
+                                    ClientMsg::CreateInstance { kind, settings } => {
+                                        if !config.host_creation {
+                                            let _ = client.sink.send(ServerMsg::CreateInstanceRejected {
+                                                reason:"instance creation is disabled".into()
                                             }).await;
+                                            continue;
+                                        }
+
+                                        let instance = {
+                                            let mut lobby = lobby.write().await;
+                                            let created = lobby.instances_created_by(client.client_id).await;
+                                            let kind = kind.unwrap_or_else(|| config.default_kind.clone());
+                                            if config.max_instances_per_creator > 0 && created >= config.max_instances_per_creator {
+                                                Err(format!("max {} instances per creator reached", config.max_instances_per_creator))
+                                            } else {
+                                                lobby.new_instance(client.client_id, &kind, &settings, config)
+                                                    .and_then(|instance_id| lobby.get_instance(instance_id).ok_or_else(|| "instance ended".into()))
+                                            }
+                                        };
+
+                                        match instance {
+                                            Ok(instance) => {
+                                                // tell the client about the new instance and join it
+                                                let _ = client.sink.send(ServerMsg::InstanceCreated {
+                                                    instance:instance.info.read().await.clone()
+                                                }).await;
+                                                if let Some(c) = instance.join(client, Vec::new()).await {
+                                                    client = c;
+                                                    pending = interval.is_some();
+                                                } else {
+                                                    break;
+                                                }
+                                            },
+                                            Err(reason) => {
+                                                let _ = client.sink.send(ServerMsg::CreateInstanceRejected {
+                                                    reason
+                                                }).await;
+                                            }
                                         }
-                                    },*/
+                                    },
                                     ClientMsg::RefreshInstances => {
+                                        let instances = lobby.read().await.instances().await;
+                                        known = instances.iter().map(|instance| (instance.id, instance.clone())).collect();
                                         let _ = client.sink.send(ServerMsg::Instances {
-                                            instances:lobby.read().await.instances().await
+                                            instances
                                         }).await;
                                     },
-                                    ClientMsg::JoinInstance { instance_id: host_id } => {
-                                        let lobby = lobby.read().await;
-                                        if let Some(host) = lobby.get_instance(host_id) {
-                                            if let Some(c) = host.join(client).await {
+                                    ClientMsg::JoinInstance { instance_id: host_id, payload } => {
+                                        // do not hold the lobby lock while the client is in the instance
+                                        let host = lobby.read().await.get_instance(host_id);
+                                        if let Some(host) = host {
+                                            if let Some(c) = host.join(client, payload).await {
                                                 client = c;
+                                                // catch up on what changed while the client was away
+                                                pending = interval.is_some();
                                             } else {
                                                 break;
                                             }
+                                        } else {
+                                            // crashed instances are still listed, tell the client why it cannot join
+                                            let crashed = lobby.read().await.crashed_instance(host_id).await;
+                                            if let Some(instance) = crashed {
+                                                let _ = client.sink.send(ServerMsg::JoinRejected {
+                                                    instance,
+                                                    reason:"instance crashed".into()
+                                                }).await;
+                                            }
                                         }
                                     },
                                     _ => {}
@@ -219,28 +612,47 @@ impl Master {
     }
 
 
-    async fn client_connected(ws: WebSocket, lobby: Arc<RwLock<Lobby>>, config:Config) {
-        let (tx, rx) = ws.split();
-        let mut tx:ClientSink = tx.into();
-        let mut stream:ClientStream = rx.into();
+    /// renders the metrics of the master in Prometheus text format
+    pub async fn render_metrics(&self) -> String {
+        let instances = self.lobby.read().await.all_instances();
+        let mut list = Vec::new();
+        for instance in instances.into_iter().filter(|instance| !instance.is_ended()) {
+            let connected = instance.players().await.iter().filter(|player| player.connected).count();
+            list.push((instance.info.read().await.clone(), connected));
+        }
+
+        self.metrics.render(self.sessions.len(), &list)
+    }
 
-        let mut id = None;
-        let mut name = "".into();
+    /// handles a client from connecting until it disconnects. `query` and `headers` are those of
+    /// the WebSocket upgrade request, empty for other transports
+    async fn client_connected(sink:FrameSink, stream:FrameStream, master:Master, query:HashMap<String, String>, headers:HashMap<String, String>) {
+        let mut tx = ClientSink::new(sink, master.metrics.clone());
+        let mut stream = ClientStream::new(stream, master.metrics.clone());
+        let sessions = master.sessions.clone();
+        let config = &master.config;
+
+        let mut request = None;
+        let mut offered = Vec::new();
 
         // wait for Hello message to get client id
-        while let Some(msg) = stream.stream.next().await {
+        while let Some(msg) = stream.recv().await {
             match msg {
-                Ok(msg) => {
-                    let bytes = msg.as_bytes();
-                    if bytes.len() > 0 {
-                        match bincode::deserialize::<ClientMsg>(bytes) {
-                            Ok(msg) => match msg {
-                                ClientMsg::Hello { client_id, client_name } => {
-                                    id = Some(client_id);
-                                    name = client_name;
-                                    break;
-                                }
-                                _ => {}
+                Ok(bytes) => {
+                    if !bytes.is_empty() {
+                        match bincode::deserialize::<ClientMsg>(&bytes) {
+                            Ok(msg) => if let ClientMsg::Hello { client_id, client_name, token, resume_token, compression } = msg {
+                                // everything after a Hello offering compression is framed
+                                stream.framing.framed = !compression.is_empty();
+                                offered = compression;
+                                request = Some((AuthRequest {
+                                    client_id,
+                                    client_name,
+                                    token,
+                                    query,
+                                    headers
+                                }, resume_token));
+                                break;
                             },
                             Err(err) => {
                                 error!("{:?}", err);
@@ -256,15 +668,79 @@ impl Master {
             }
         }
 
-        if let Some(client_id) = id {
-            // Hello received, send Welcome message
-            // and proceed to lobby if successfull
-            let msg = ServerMsg::JoinedLobby {};
-            match tx.send(msg).await {
-                Ok(_) => {
-                    Self::client_joined_lobby(Client{sink: tx, stream, client_id, client_name: name}, lobby, config).await
+        let mut id = None;
+        if let Some((request, resume_token)) = request {
+            // Hello received, authenticate the client unless it resumes a previous session
+            let resumed = match resume_token {
+                Some(token) => sessions.resume(&token).await,
+                None => None
+            };
+            let identity = match resumed {
+                Some(identity) => Ok(identity),
+                None => config.authenticator.authenticate(&request).await
+            };
+            let res = match identity {
+                Ok(identity) => sessions.begin(&identity, config.duplicate_session).await.map(|session| (identity, session)),
+                Err(reason) => Err(reason)
+            };
+            match res {
+                Ok((identity, session)) => {
+                    let reconnect_grace = config.reconnect_grace;
+                    // send Welcome message
+                    // and proceed to lobby if successfull
+                    id = Some(identity.client_id);
+                    let codec = match (config.compression_threshold, offered.contains(&Compression::Lz4)) {
+                        (Some(threshold), true) => Some(Codec {
+                            compression:Compression::Lz4,
+                            threshold
+                        }),
+                        _ => None
+                    };
+                    let msg = ServerMsg::JoinedLobby {
+                        client_id:identity.client_id,
+                        resume_token:session.resume_token().into(),
+                        compression:codec
+                    };
+                    let udp = master.udp.register();
+                    let sent = tx.send(msg).await;
+
+                    // the master compresses using the chosen codec from now on, as may the client
+                    tx.framing = Framing {
+                        framed:codec.is_some(),
+                        codec
+                    };
+                    let sent = match sent {
+                        Ok(_) => match &udp {
+                            Some(udp) => tx.send(ServerMsg::UdpAvailable { port:udp.port(), key:udp.key() }).await,
+                            None => Ok(())
+                        },
+                        Err(err) => Err(err)
+                    };
+                    tx.udp = udp.clone();
+                    match sent {
+                        Ok(_) => {
+                            Self::client_joined_lobby(Client{sink: tx, stream, client_id:identity.client_id, client_name: identity.client_name, session:session.clone()}, master.clone()).await
+                        },
+                        Err(_) => error!("Client {} failed to join", identity.client_id),
+                    }
+
+                    if let Some(udp) = udp {
+                        master.udp.unregister(udp.key());
+                    }
+
+                    // a kicked client must authenticate again
+                    let grace = match session.is_revoked() {
+                        true => None,
+                        false => reconnect_grace
+                    };
+                    sessions.end(identity.client_id, &session, grace);
                 },
-                Err(_) => error!("Client {} failed to join", client_id),
+                Err(reason) => {
+                    info!("Client {} rejected: {}", request.client_id, reason);
+                    let _ = tx.send(ServerMsg::HelloRejected {
+                        reason
+                    }).await;
+                }
             }
         }
 
@@ -278,25 +754,128 @@ impl Master {
 
     /// Starts a web server listening on the `addr` supplied in the `new()`
     /// 
-    /// Accepts WebSocket upgrades on any address.
+    /// Accepts WebSocket upgrades on `Http::ws_path`, any path by default
+    /// 
+    /// Accepts raw TCP clients on `Config::tcp_addr`, if set
+    /// 
+    /// Sends unreliable messages over UDP from `Config::udp_addr`, if set
+    /// 
+    /// Serves static files as set in `Config::http`, from the `./public` directory by default
     /// 
-    /// Serves static files from the `./public` directory
+    /// Serves the JSON admin API below `/admin` if `Config::admin_token` is set
+    /// 
+    /// Serves Prometheus metrics on `/metrics` if `Config::metrics` is set
+    /// 
+    /// Restores the instances stored in `Config::snapshot_dir`, if set
+    /// 
+    /// Serves everything over TLS if `Config::tls` is set, panics if the certificate cannot be loaded
+    /// 
+    /// The returned handle resolves once the master has been shut down using the `shutdown_handle()`
+    /// and all instances have ended
     pub fn start(self) -> JoinHandle<()> {
-        return tokio::spawn(async move {
+        tokio::spawn(async move {
+            self.restore().await;
+
+            self.serve_tcp();
+            self.serve_udp().await;
             let addr = SocketAddr::from_str(&self.addr).expect("Could not parse address");
+            let routes = self.routes();
+            let shutdown = self.shutdown.clone();
+            let server = match self.config.tls.clone() {
+                Some(tls) => {
+                    let acceptor = tls::acceptor(tls, self.shutdown.clone())
+                        .unwrap_or_else(|err| panic!("Could not load TLS certificate: {}", err));
+                    let listener = tokio::net::TcpListener::bind(addr).await.expect("Could not bind address");
+                    let incoming = tls::incoming(listener, acceptor, self.shutdown.clone());
+                    tokio::spawn(warp::serve(routes).serve_incoming_with_graceful_shutdown(incoming, async move {
+                        shutdown.wait().await;
+                    }))
+                },
+                None => {
+                    let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(addr, async move {
+                        shutdown.wait().await;
+                    });
+                    tokio::spawn(server)
+                }
+            };
 
-            let public_route = warp::fs::dir("./public");
-            let lobby = self.lobby.clone();
-            let config = self.config.clone();
-            let ws_route = warp::ws().map(move |ws: warp::ws::Ws| {
-                let lobby = lobby.clone();
-                let config = config.clone();
-                ws.on_upgrade(move |ws| Self::client_connected(ws, lobby, config))
-            });
+            self.run().await;
+            let _ = server.await;
+        })
+    }
 
-            let routes = warp::get().and(ws_route).or(public_route);
+    /// Starts the master without a web server of its own, for when `routes()` are served by
+    /// an existing warp server. The `addr` supplied in `new()` and `Config::tls` are not used,
+    /// raw TCP clients and UDP are still served on `Config::tcp_addr` and `Config::udp_addr`, if set
+    /// 
+    /// The returned handle resolves once the master has been shut down using the `shutdown_handle()`
+    /// and all instances have ended
+    pub fn start_embedded(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            self.restore().await;
+            self.serve_tcp();
+            self.serve_udp().await;
+            self.run().await;
+        })
+    }
 
-            warp::serve(routes).run(addr).await;
+    /// returns the routes of the master as configured in `Config::http`, to be mounted
+    /// in an existing warp server together with `start_embedded()`
+    pub fn routes(&self) -> Routes {
+        http::routes(self.clone())
+    }
+
+    fn serve_tcp(&self) {
+        if let Some(addr) = &self.config.tcp_addr {
+            let addr = SocketAddr::from_str(addr).expect("Could not parse TCP address");
+            tokio::spawn(transport::serve_tcp(self.clone(), addr));
+        }
+    }
+
+    async fn serve_udp(&self) {
+        if let Some(addr) = &self.config.udp_addr {
+            let addr = SocketAddr::from_str(addr).expect("Could not parse UDP address");
+            self.udp.bind(addr).await;
+            tokio::spawn(self.udp.clone().serve(self.shutdown.clone()));
+        }
+    }
+
+    async fn restore(&self) {
+        let restored = self.restore_instances().await;
+        if restored > 0 {
+            info!("Restored {} instances from snapshots", restored);
+        }
+    }
+
+    /// waits for shutdown, then ends the instances and disconnects the clients
+    async fn run(&self) {
+        // no new connections are accepted after this
+        let reason = self.shutdown.wait().await;
+        info!("Shutting down: {}", reason);
+
+        // end the instances first, such that the messages of `Server::shutdown` reach their clients
+        self.suspend_instances(&reason).await;
+
+        // then disconnect the clients, which are back in the lobby
+        self.sessions.disconnect_all(ServerMsg::ServerShuttingDown {
+            reason:reason.clone()
         });
+        let _ = tokio::time::timeout(Duration::from_secs(5), self.sessions.wait_empty()).await;
+
+        // instances created from the lobby in the meantime are ended as well
+        self.suspend_instances(&reason).await;
+
+        info!("Shut down");
+    }
+
+    /// ends all instances, keeping their snapshots, and waits for them to finish
+    async fn suspend_instances(&self, reason:&str) {
+        let instances = self.lobby.write().await.remove_all();
+        for instance in instances.iter() {
+            instance.suspend(reason).await;
+        }
+        for instance in instances.iter() {
+            instance.ended().await;
+        }
     }
 }
@@ -0,0 +1,272 @@
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}, time::{Duration, Instant}};
+
+use tokio::sync::{Notify, watch};
+use uuid::Uuid;
+
+use crate::client::ServerMsg;
+
+use super::Identity;
+
+/// what to do when a client connects with an identity which already has a session
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateSession {
+    /// kick the existing session with `ServerMsg::Kicked` and let the new one in
+    KickOld,
+
+    /// reject the new session with `ServerMsg::HelloRejected`
+    RejectNew
+}
+
+/// a single connected and authenticated client
+pub struct Session {
+    resume_token:String,
+    disconnect:Notify,
+    farewell:Mutex<Option<ServerMsg>>,
+
+    /// set when a new connection resumes the session while it is still connected
+    taken_over:AtomicBool,
+
+    /// set when the client must not resume the session, e.g. because it was kicked
+    revoked:AtomicBool,
+    closed:watch::Sender<bool>
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            resume_token:Uuid::new_v4().to_string(),
+            disconnect:Notify::new(),
+            farewell:Mutex::new(None),
+            taken_over:AtomicBool::new(false),
+            revoked:AtomicBool::new(false),
+            closed:watch::channel(false).0
+        }
+    }
+
+    /// the token which the client can send in `Hello` to resume this session after a disconnect
+    pub fn resume_token(&self) -> &str {
+        &self.resume_token
+    }
+
+    /// asks the session to disconnect its client with `ServerMsg::Kicked`, revoking its resume token
+    pub fn kick(&self, reason:&str) {
+        self.revoke();
+        self.disconnect(ServerMsg::Kicked {
+            reason:reason.into()
+        });
+    }
+
+    /// revokes the resume token, such that the session cannot be resumed once it has ended
+    pub fn revoke(&self) {
+        self.revoked.store(true, Ordering::Relaxed);
+    }
+
+    /// true if the resume token has been revoked
+    pub fn is_revoked(&self) -> bool {
+        self.revoked.load(Ordering::Relaxed)
+    }
+
+    /// asks the session to leave its instance, send `farewell` to its client and disconnect
+    pub fn disconnect(&self, farewell:ServerMsg) {
+        *self.farewell.lock().unwrap() = Some(farewell);
+        self.disconnect.notify_one();
+    }
+
+    /// asks the session to disconnect its client like a lost connection, such that its seat is held
+    /// for the new connection resuming it
+    fn take_over(&self) {
+        self.taken_over.store(true, Ordering::Relaxed);
+        self.disconnect(ServerMsg::Kicked {
+            reason:"resumed from another connection".into()
+        });
+    }
+
+    /// true if the session was disconnected because a new connection resumed it
+    pub fn is_taken_over(&self) -> bool {
+        self.taken_over.load(Ordering::Relaxed)
+    }
+
+    /// waits until the session is asked to disconnect, returning the farewell message
+    pub async fn disconnected(&self) -> ServerMsg {
+        self.disconnect.notified().await;
+        match self.farewell.lock().unwrap().clone() {
+            Some(farewell) => farewell,
+            None => ServerMsg::Kicked {
+                reason:"".into()
+            }
+        }
+    }
+}
+
+/// keeps track of the session of each identity currently connected
+#[derive(Default)]
+pub struct Sessions {
+    sessions:Mutex<HashMap<Uuid, Arc<Session>>>,
+
+    /// identities by resume token, with the time the token expires.
+    /// tokens of connected sessions do not expire
+    resume_tokens:Mutex<HashMap<String, (Identity, Option<Instant>)>>,
+
+    /// set once all sessions are disconnected, rejecting new sessions
+    closed:Mutex<bool>,
+
+    /// notified whenever a session ends
+    ended:Notify,
+
+    /// identities which are not allowed to start a session
+    banned:Mutex<HashSet<Uuid>>
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// starts a session for `client_id`, resolving a duplicate session according to `policy`
+    ///
+    /// returns the reason if the new session is rejected
+    pub async fn begin(&self, identity:&Identity, policy:DuplicateSession) -> Result<Arc<Session>, String> {
+        if *self.closed.lock().unwrap() {
+            return Err("server is shutting down".into());
+        }
+
+        if self.banned.lock().unwrap().contains(&identity.client_id) {
+            return Err("banned".into());
+        }
+
+        let client_id = identity.client_id;
+        let existing = self.sessions.lock().unwrap().get(&client_id).cloned();
+        if let Some(existing) = existing {
+            match policy {
+                DuplicateSession::RejectNew => return Err("already connected".into()),
+                DuplicateSession::KickOld => {
+                    // wait for the old session to leave its instance, such that
+                    // the identity is never present twice
+                    let mut closed = existing.closed.subscribe();
+                    existing.kick("logged in from another connection");
+                    let _ = tokio::time::timeout(Duration::from_secs(5), closed.wait_for(|closed| *closed)).await;
+                }
+            }
+        }
+
+        let session = Arc::new(Session::new());
+        let mut sessions = self.sessions.lock().unwrap();
+        if policy == DuplicateSession::RejectNew && sessions.contains_key(&client_id) {
+            return Err("already connected".into());
+        }
+
+        sessions.insert(client_id, session.clone());
+
+        let mut resume_tokens = self.resume_tokens.lock().unwrap();
+        let now = Instant::now();
+        resume_tokens.retain(|_, (_, expires)| expires.is_none_or(|expires| expires > now));
+        resume_tokens.insert(session.resume_token.clone(), (identity.clone(), None));
+        Ok(session)
+    }
+
+    /// ends the session, removing it unless it has been replaced by a newer session.
+    /// the resume token of the session stays valid for `grace`, `None` revokes it
+    pub fn end(&self, client_id:Uuid, session:&Arc<Session>, grace:Option<Duration>) {
+        session.closed.send_replace(true);
+        {
+            let mut resume_tokens = self.resume_tokens.lock().unwrap();
+            match grace {
+                Some(grace) => {
+                    if let Some((_, expires)) = resume_tokens.get_mut(&session.resume_token) {
+                        *expires = Some(Instant::now() + grace);
+                    }
+                },
+                None => {
+                    resume_tokens.remove(&session.resume_token);
+                }
+            }
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(current) = sessions.get(&client_id) {
+            if Arc::ptr_eq(current, session) {
+                sessions.remove(&client_id);
+            }
+        }
+
+        self.ended.notify_waiters();
+    }
+
+    /// waits until no sessions are connected
+    pub async fn wait_empty(&self) {
+        loop {
+            let ended = self.ended.notified();
+            tokio::pin!(ended);
+            ended.as_mut().enable();
+            if self.is_empty() {
+                return;
+            }
+
+            ended.await;
+        }
+    }
+
+    /// consumes a resume token, returning the identity of its session
+    ///
+    /// a session which is still connected, e.g. after a half-open disconnect, is taken over:
+    /// it is disconnected like a lost connection, holding its seat, and ended before returning.
+    ///
+    /// returns `None` if the token is unknown, expired or revoked
+    pub async fn resume(&self, resume_token:&str) -> Option<Identity> {
+        let (identity, expires) = self.resume_tokens.lock().unwrap().get(resume_token).cloned()?;
+        if expires.is_none() {
+            let existing = self.get(identity.client_id).filter(|session| session.resume_token == resume_token && !session.is_revoked())?;
+            let mut closed = existing.closed.subscribe();
+            existing.take_over();
+            let _ = tokio::time::timeout(Duration::from_secs(5), closed.wait_for(|closed| *closed)).await;
+            self.resume_tokens.lock().unwrap().remove(resume_token);
+            return Some(identity);
+        }
+
+        let mut resume_tokens = self.resume_tokens.lock().unwrap();
+        match resume_tokens.get(resume_token) {
+            Some((_, Some(expires))) if *expires > Instant::now() => {
+                resume_tokens.remove(resume_token).map(|(identity, _)| identity)
+            },
+            _ => None
+        }
+    }
+
+    /// bans `client_id` from starting new sessions and kicks its current session, if any
+    ///
+    /// only effective with an `Authenticator` which resolves a client to the same id on every connection.
+    /// with the default `AssignIds` a banned client simply reconnects with a fresh id
+    pub fn ban(&self, client_id:Uuid, reason:&str) {
+        self.banned.lock().unwrap().insert(client_id);
+        if let Some(session) = self.get(client_id) {
+            session.kick(reason);
+        }
+    }
+
+    /// lifts the ban of `client_id`, returning false if it was not banned
+    pub fn unban(&self, client_id:Uuid) -> bool {
+        self.banned.lock().unwrap().remove(&client_id)
+    }
+
+    /// disconnects every session with `farewell` and rejects new sessions from now on
+    pub fn disconnect_all(&self, farewell:ServerMsg) {
+        *self.closed.lock().unwrap() = true;
+        for session in self.sessions.lock().unwrap().values() {
+            session.disconnect(farewell.clone());
+        }
+    }
+
+    /// returns the session of `client_id` if connected
+    pub fn get(&self, client_id:Uuid) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(&client_id).cloned()
+    }
+
+    /// returns the number of connected sessions
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
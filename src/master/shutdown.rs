@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use log::info;
+use tokio::sync::watch;
+
+/// handle used to gracefully shut down a running `Master`
+///
+/// obtained using `Master::shutdown_handle()` and can be cloned freely
+#[derive(Clone)]
+pub struct Shutdown {
+    reason:Arc<watch::Sender<Option<String>>>
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            reason:Arc::new(watch::channel(None).0)
+        }
+    }
+
+    /// stops accepting connections, ends all instances and tells clients the server
+    /// is going down with `reason`. only the first call has any effect
+    pub fn shutdown(&self, reason:&str) {
+        self.reason.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+
+            *current = Some(reason.into());
+            true
+        });
+    }
+
+    /// returns true if shutdown has been requested
+    pub fn is_shutting_down(&self) -> bool {
+        self.reason.borrow().is_some()
+    }
+
+    /// shuts down once the process receives SIGINT (ctrl-c) or SIGTERM
+    pub fn on_signal(&self) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            let reason = wait_for_signal().await;
+            info!("Received {}, shutting down", reason);
+            shutdown.shutdown(&format!("server received {}", reason));
+        });
+    }
+
+    /// waits until shutdown has been requested, returning the reason
+    pub async fn wait(&self) -> String {
+        let mut receiver = self.reason.subscribe();
+        let reason = receiver.wait_for(|reason| reason.is_some()).await;
+        match reason {
+            Ok(reason) => reason.clone().unwrap_or_default(),
+            Err(_) => String::new()
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = terminate.recv() => "SIGTERM"
+            }
+        },
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+            "SIGINT"
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "SIGINT"
+}
@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{bincoded::Bincoded, shared::InstanceInfo};
+
+/// the stored state of an instance, see `Server::snapshot`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub info:InstanceInfo,
+    pub state:Vec<u8>
+}
+
+impl Bincoded for Snapshot {
+}
+
+fn path(dir:&Path, id:Uuid) -> PathBuf {
+    dir.join(format!("{}.snapshot", id))
+}
+
+/// writes the snapshot to `dir`, replacing the previous snapshot of the instance
+pub async fn save(dir:&Path, snapshot:&Snapshot) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+
+    // write to a temporary file first, such that a crash never leaves a partial snapshot behind
+    let path = path(dir, snapshot.info.id);
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, snapshot.to_bincode()).await?;
+    tokio::fs::rename(&tmp, &path).await
+}
+
+/// removes the snapshot of the instance `id` from `dir`, if any
+pub async fn remove(dir:&Path, id:Uuid) {
+    let _ = tokio::fs::remove_file(path(dir, id)).await;
+}
+
+/// loads all snapshots stored in `dir`, skipping files which cannot be read
+pub async fn load_all(dir:&Path) -> Vec<Snapshot> {
+    let mut snapshots = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(_) => return snapshots
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "snapshot") {
+            continue;
+        }
+
+        match tokio::fs::read(&path).await.ok().and_then(|bytes| Snapshot::from_bincode(&bytes)) {
+            Some(snapshot) => snapshots.push(snapshot),
+            None => error!("Could not read snapshot {:?}", path)
+        }
+    }
+
+    snapshots
+}
@@ -0,0 +1,162 @@
+use std::{fs::File, io::{self, BufReader}, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::{Duration, SystemTime}};
+
+use futures_util::Stream;
+use log::{debug, error, info, warn};
+use tokio::{net::{TcpListener, TcpStream}, select, sync::mpsc};
+use tokio_rustls::{TlsAcceptor, rustls::{self, ServerConfig, server::{ClientHello, ResolvesServerCert}, sign::CertifiedKey}, server::TlsStream};
+
+use super::Shutdown;
+
+/// max time a client has to complete the TLS handshake
+const HANDSHAKE_TIMEOUT:Duration = Duration::from_secs(10);
+
+/// serves the master over TLS (https and wss) using a PEM certificate and key
+#[derive(Clone, Debug)]
+pub struct Tls {
+    /// PEM file with the certificate chain, leaf first
+    pub cert:PathBuf,
+
+    /// PEM file with the private key of the certificate
+    pub key:PathBuf,
+
+    /// how often the files are checked for changes. changed files are reloaded
+    /// without interrupting connected clients, new connections use the new certificate
+    pub reload_interval:Duration
+}
+
+impl Tls {
+    pub fn new(cert:impl Into<PathBuf>, key:impl Into<PathBuf>) -> Self {
+        Self {
+            cert:cert.into(),
+            key:key.into(),
+            reload_interval:Duration::from_secs(10)
+        }
+    }
+}
+
+/// hands out the currently loaded certificate
+#[derive(Debug)]
+struct Resolver {
+    key:RwLock<Arc<CertifiedKey>>
+}
+
+impl ResolvesServerCert for Resolver {
+    fn resolve(&self, _client_hello:ClientHello) -> Option<Arc<CertifiedKey>> {
+        let key = self.key.read().ok()?;
+        Some(key.clone())
+    }
+}
+
+/// loads the certificate chain and key from the PEM files of `tls`
+fn load(tls:&Tls) -> Result<CertifiedKey, String> {
+    let open = |path:&Path| File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| format!("could not open {}: {}", path.display(), err));
+
+    let certs = rustls_pemfile::certs(&mut open(&tls.cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not read {}: {}", tls.cert.display(), err))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", tls.cert.display()));
+    }
+
+    let key = rustls_pemfile::private_key(&mut open(&tls.key)?)
+        .map_err(|err| format!("could not read {}: {}", tls.key.display(), err))?
+        .ok_or_else(|| format!("no private key found in {}", tls.key.display()))?;
+    let key = rustls::crypto::ring::sign::any_supported_type(&key)
+        .map_err(|err| format!("unsupported private key in {}: {}", tls.key.display(), err))?;
+
+    Ok(CertifiedKey::new(certs, key))
+}
+
+/// returns the modification times of the files of `tls`
+fn modified(tls:&Tls) -> Option<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(&tls.cert).ok()?.modified().ok()?;
+    let key = std::fs::metadata(&tls.key).ok()?.modified().ok()?;
+    Some((cert, key))
+}
+
+/// loads the certificate of `tls` and returns an acceptor which picks up
+/// changes to the files until `shutdown`
+pub(crate) fn acceptor(tls:Tls, shutdown:Shutdown) -> Result<TlsAcceptor, String> {
+    let mut loaded = modified(&tls);
+    let resolver = Arc::new(Resolver {
+        key:RwLock::new(Arc::new(load(&tls)?))
+    });
+
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tls.reload_interval);
+        loop {
+            select! {
+                _ = interval.tick() => {},
+                _ = shutdown.wait() => break
+            }
+
+            let current = modified(&tls);
+            if current == loaded {
+                continue;
+            }
+
+            // the files might be replaced one at a time, a failed load
+            // keeps the old certificate and is retried on the next check
+            match load(&tls) {
+                Ok(key) => {
+                    if let Ok(mut current) = resolver.key.write() {
+                        *current = Arc::new(key);
+                    }
+                    loaded = current;
+                    info!("Reloaded TLS certificate {}", tls.cert.display());
+                },
+                Err(err) => warn!("Could not reload TLS certificate: {}", err)
+            }
+        }
+    });
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// accepts connections on `listener` until `shutdown`, yielding those which completed the TLS handshake.
+/// handshakes run concurrently, such that a slow client does not hold up others
+pub(crate) fn incoming(listener:TcpListener, acceptor:TlsAcceptor, shutdown:Shutdown) -> impl Stream<Item = Result<TlsStream<TcpStream>, io::Error>> {
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            let accepted = select! {
+                accepted = listener.accept() => accepted,
+                _ = shutdown.wait() => break
+            };
+
+            match accepted {
+                Ok((stream, addr)) => {
+                    let acceptor = acceptor.clone();
+                    let tx = tx.clone();
+                    tokio::spawn(async move {
+                        match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                            Ok(Ok(stream)) => {
+                                let _ = tx.send(stream).await;
+                            },
+                            Ok(Err(err)) => debug!("TLS handshake with {} failed: {}", addr, err),
+                            Err(_) => debug!("TLS handshake with {} timed out", addr)
+                        }
+                    });
+                },
+                Err(err) => {
+                    // e.g. out of file descriptors, back off instead of spinning
+                    error!("Could not accept connection: {}", err);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        let stream = rx.recv().await?;
+        Some((Ok(stream), rx))
+    })
+}
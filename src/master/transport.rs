@@ -0,0 +1,54 @@
+use std::{collections::HashMap, io, net::SocketAddr, time::Duration};
+
+use futures_util::{SinkExt, StreamExt, future};
+use log::{error, info};
+use tokio::{net::TcpListener, select};
+use warp::ws::{Message, WebSocket};
+
+use crate::frame::tcp;
+pub use crate::frame::{FrameSink, FrameStream};
+
+use super::Master;
+
+fn to_io(err:warp::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+/// frames are sent as binary messages. ping, pong and close messages are not passed on
+pub(crate) fn websocket(ws:WebSocket) -> (FrameSink, FrameStream) {
+    let (sink, stream) = ws.split();
+    let sink = sink
+        .sink_map_err(to_io)
+        .with(|frame:Vec<u8>| future::ready(Ok::<_, io::Error>(Message::binary(frame))));
+    let stream = stream.filter_map(|msg| future::ready(match msg {
+        Ok(msg) if msg.is_binary() || msg.is_text() => Some(Ok(msg.into_bytes())),
+        Ok(_) => None,
+        Err(err) => Some(Err(to_io(err)))
+    }));
+
+    (Box::pin(sink), Box::pin(stream))
+}
+
+/// accepts raw TCP clients on `addr` until the master shuts down
+pub(crate) async fn serve_tcp(master:Master, addr:SocketAddr) {
+    let listener = TcpListener::bind(addr).await.expect("Could not bind TCP address");
+    info!("Accepting TCP clients on {}", addr);
+    loop {
+        let accepted = select! {
+            accepted = listener.accept() => accepted,
+            _ = master.shutdown.wait() => break
+        };
+
+        match accepted {
+            Ok((stream, _)) => {
+                let (sink, stream) = tcp(stream);
+                tokio::spawn(Master::client_connected(sink, stream, master.clone(), HashMap::new(), HashMap::new()));
+            },
+            Err(err) => {
+                // e.g. out of file descriptors, back off instead of spinning
+                error!("Could not accept TCP client: {}", err);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, net::SocketAddr, sync::{Arc, Mutex, OnceLock}};
+
+use log::{debug, info};
+use tokio::{net::UdpSocket, select};
+use uuid::Uuid;
+
+use crate::{bincoded::Bincoded, client::UdpMsg};
+
+use super::Shutdown;
+
+/// largest datagram sent to clients, larger messages are sent over the connection.
+/// small enough to avoid fragmentation on common links
+pub const MAX_DATAGRAM_LEN:usize = 1200;
+
+/// the UDP side channel of a master, see `Config::udp_addr`
+#[derive(Default)]
+pub(crate) struct Udp {
+    socket:OnceLock<Arc<UdpSocket>>,
+
+    /// the routes of the connected clients by key
+    routes:Mutex<HashMap<Uuid, UdpRoute>>
+}
+
+/// the UDP route to a single client, unusable until the client has registered its address
+#[derive(Clone)]
+pub(crate) struct UdpRoute {
+    socket:Arc<UdpSocket>,
+    key:Uuid,
+    peer:Arc<Mutex<Option<SocketAddr>>>
+}
+
+impl UdpRoute {
+    pub fn key(&self) -> Uuid {
+        self.key
+    }
+
+    pub fn port(&self) -> u16 {
+        self.socket.local_addr().map(|addr| addr.port()).unwrap_or_default()
+    }
+
+    /// sends `datagram` to the client, returns false if it has not registered its address or sending failed
+    pub async fn send(&self, datagram:&[u8]) -> bool {
+        let peer = *self.peer.lock().unwrap();
+        match peer {
+            Some(peer) => self.socket.send_to(datagram, peer).await.is_ok(),
+            None => false
+        }
+    }
+}
+
+impl Udp {
+    /// returns a new route for a client, `None` if the side channel is not enabled
+    pub fn register(&self) -> Option<UdpRoute> {
+        let socket = self.socket.get()?.clone();
+        let route = UdpRoute {
+            socket,
+            key:Uuid::new_v4(),
+            peer:Arc::new(Mutex::new(None))
+        };
+        self.routes.lock().unwrap().insert(route.key, route.clone());
+        Some(route)
+    }
+
+    /// forgets the route of a disconnected client
+    pub fn unregister(&self, key:Uuid) {
+        if let Some(route) = self.routes.lock().unwrap().remove(&key) {
+            *route.peer.lock().unwrap() = None;
+        }
+    }
+
+    /// binds the socket, enabling the side channel
+    pub async fn bind(&self, addr:SocketAddr) {
+        let socket = UdpSocket::bind(addr).await.expect("Could not bind UDP address");
+        info!("Accepting UDP datagrams on {}", addr);
+        let _ = self.socket.set(Arc::new(socket));
+    }
+
+    /// registers the addresses of the clients until `shutdown`
+    pub async fn serve(self:Arc<Self>, shutdown:Shutdown) {
+        let socket = match self.socket.get() {
+            Some(socket) => socket.clone(),
+            None => return
+        };
+
+        let mut buf = vec![0; 2048];
+        loop {
+            let received = select! {
+                received = socket.recv_from(&mut buf) => received,
+                _ = shutdown.wait() => break
+            };
+
+            let (len, from) = match received {
+                Ok(received) => received,
+                // e.g. ICMP port unreachable from a client which went away
+                Err(_) => continue
+            };
+
+            if let Some(UdpMsg::Hello { key }) = UdpMsg::from_bincode(&buf[..len]) {
+                let route = self.routes.lock().unwrap().get(&key).cloned();
+                match route {
+                    Some(route) => {
+                        let previous = route.peer.lock().unwrap().replace(from);
+                        if previous != Some(from) {
+                            debug!("UDP address of key {} is {}", key, from);
+                        }
+
+                        let _ = socket.send_to(&UdpMsg::Welcome.to_bincode(), from).await;
+                    },
+                    None => debug!("Unknown UDP key from {}", from)
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,15 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
 use uuid::Uuid;
 
+use crate::bincoded::Bincoded;
+
+mod typed;
+pub use typed::*;
+
+/// messages from the master to the server.
+/// `T` is the type of the custom messages sent by clients, raw bytes unless using a `TypedServer`
 #[derive(Clone, Debug)]
-pub enum InMsg {
+pub enum InMsg<T = Vec<u8>> {
     ClientJoined {
         client_id:Uuid,
         client_name:String
@@ -10,93 +17,283 @@ pub enum InMsg {
     ClientLeft {
         client_id:Uuid
     },
+
+    /// the connection of the client was lost, but its seat is held during the reconnect grace period.
+    /// followed by either `ClientReconnected` or `ClientLeft`
+    ClientDisconnected {
+        client_id:Uuid
+    },
+    ClientReconnected {
+        client_id:Uuid
+    },
     CustomMsg {
         client_id:Uuid,
-        msg:Vec<u8>
+        msg:T
+    },
+
+    /// a custom message of the client could not be decoded into the `ClientInput` of a `TypedServer`
+    InvalidMsg {
+        client_id:Uuid,
+        msg:Vec<u8>,
+        error:String
     }
 }
 
+/// messages from the server to the master.
+/// `T` is the type of the custom messages sent to clients, raw bytes unless using a `TypedServer`
 #[derive(Clone, Debug)]
-pub enum OutMsg {
+pub enum OutMsg<T = Vec<u8>> {
     CustomToAll {
-        msg:Vec<u8>
+        msg:T
     },
     CustomTo {
         client_id:Uuid,
-        msg:Vec<u8>
+        msg:T
+    },
+
+    /// removes the client from the instance, sending it `ServerMsg::Kicked` with `reason`.
+    /// the client is returned to the lobby, or disconnected if `disconnect` is true.
+    /// `InMsg::ClientLeft` follows in the next tick
+    Kick {
+        client_id:Uuid,
+        reason:String,
+        disconnect:bool
+    },
+
+    /// like `CustomToAll`, but sent over the UDP side channel of the clients which have one,
+    /// such that a lost message does not hold up later ones. it might be lost or arrive out of order.
+    /// sent like `CustomToAll` to clients without UDP and if too large for a single datagram
+    UnreliableToAll {
+        msg:T
+    },
+
+    /// like `CustomTo`, but unreliable, see `UnreliableToAll`
+    UnreliableTo {
+        client_id:Uuid,
+        msg:T
     }
 }
 
+/// the shortest tick period, shorter periods are raised to this
+pub const MIN_TICK_PERIOD:Duration = Duration::from_millis(1);
+
+/// the longest tick period, longer periods are lowered to this
+pub const MAX_TICK_PERIOD:Duration = Duration::from_secs(60 * 60);
+
 #[derive(Debug)]
 pub struct Config {
-    pub tick_rate:u64,
-    pub max_players:u32
+    /// time between two ticks, can be changed later using `Ctx::set_tick_period`
+    pub tick_period:Duration,
+    pub max_players:u32,
+
+    /// how the instance is ticked while no clients are seated
+    pub idle:Idle,
+
+    /// how `Ctx::delta` is computed
+    pub timestep:Timestep
 }
 
-pub struct Ctx {
-    pub(crate) in_messages:VecDeque<InMsg>,
-    pub(crate) out_messages:VecDeque<OutMsg>,
+impl Default for Config {
+    /// ticks at 20 Hz with a variable timestep, also while empty, without a player limit
+    fn default() -> Self {
+        Self {
+            tick_period:tick_rate(20.0),
+            max_players:u32::MAX,
+            idle:Idle::default(),
+            timestep:Timestep::default()
+        }
+    }
+}
+
+/// how time advances between ticks
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Timestep {
+    /// `Ctx::delta` is the wall-clock time since the last tick. ticks missed
+    /// because the server fell behind are skipped
+    #[default]
+    Variable,
+
+    /// `Ctx::delta` is always the tick period, making simulations reproducible.
+    /// ticks missed because the server fell behind are run back to back, at most
+    /// `max_catch_up` at a time, the rest are dropped. an instance idling with
+    /// `Idle::Period` runs a single tick per wakeup
+    Fixed {
+        max_catch_up:u32
+    }
+}
+
+/// how an instance without seated clients is ticked, to save CPU on servers with many idle instances
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Idle {
+    /// keep ticking with the tick period
+    #[default]
+    Tick,
+
+    /// tick with the given, usually longer, period until a client joins
+    Period(Duration),
+
+    /// do not tick at all until a client joins
+    Sleep
+}
+
+/// returns the tick period of the given rate in ticks per second,
+/// limited to `MIN_TICK_PERIOD` and `MAX_TICK_PERIOD`
+pub fn tick_rate(ticks_per_second:f64) -> Duration {
+    if ticks_per_second.is_nan() || ticks_per_second <= 1.0 / MAX_TICK_PERIOD.as_secs_f64() {
+        return MAX_TICK_PERIOD;
+    }
+
+    clamp_tick_period(Duration::from_secs_f64(1.0 / ticks_per_second))
+}
+
+/// limits the `period` to `MIN_TICK_PERIOD` and `MAX_TICK_PERIOD`
+pub fn clamp_tick_period(period:Duration) -> Duration {
+    period.clamp(MIN_TICK_PERIOD, MAX_TICK_PERIOD)
+}
+
+pub struct Ctx<I = Vec<u8>, O = Vec<u8>> {
+    pub(crate) in_messages:VecDeque<InMsg<I>>,
+    pub(crate) out_messages:VecDeque<OutMsg<O>>,
 
     /// delta time between ticks in seconds between ticks
     /// this value can go from close zero to many thousands 
     /// and needs to be truncated or similar by the consumer to avoid
-    /// unintended behavior, e.g. players jumping through walls due to high tick
+    /// unintended behavior, e.g. players jumping through walls due to high tick.
+    /// constant with `Timestep::Fixed`
     pub delta:f64,
-    pub time:f64
+    pub time:f64,
+
+    /// number of ticks run before the current one
+    pub tick:u64,
+
+    pub(crate) tick_period:Duration,
+    pub(crate) ended:Option<String>
 }
 
-impl Ctx {
-    pub fn pop_msg(&mut self) -> Option<InMsg> {
-        let msg = self.in_messages.pop_front();
-        return msg;
+impl<I, O> Ctx<I, O> {
+    pub fn pop_msg(&mut self) -> Option<InMsg<I>> {
+        self.in_messages.pop_front()
     }
 
-    pub fn push_msg(&mut self, msg:OutMsg) {
+    #[allow(clippy::useless_conversion)]
+    pub fn push_msg(&mut self, msg:OutMsg<O>) {
         let msg = msg.into();
         self.out_messages.push_back(msg);
     }
 
-    pub fn pop_all(&mut self) -> VecDeque<InMsg> {
+    /// declares the instance as finished.
+    /// after the current tick the instance is destroyed and all clients are sent back
+    /// to the lobby with `reason`
+    pub fn end(&mut self, reason:&str) {
+        self.ended = Some(reason.into());
+    }
+
+    /// returns the current time between two ticks
+    pub fn tick_period(&self) -> Duration {
+        self.tick_period
+    }
+
+    /// changes the time between two ticks, starting after the current tick
+    pub fn set_tick_period(&mut self, period:Duration) {
+        self.tick_period = clamp_tick_period(period);
+    }
+
+    /// changes the number of ticks per second, see `set_tick_period`
+    pub fn set_tick_rate(&mut self, ticks_per_second:f64) {
+        self.set_tick_period(tick_rate(ticks_per_second));
+    }
+}
+
+impl<I:Clone, O> Ctx<I, O> {
+    pub fn pop_all(&mut self) -> VecDeque<InMsg<I>> {
         let cloned = self.in_messages.clone();
         self.in_messages.clear();
-        return cloned;
+        cloned
     }
 }
 
 pub trait Server : Send + Sync + 'static {
     fn init(&mut self) -> Config;
     fn tick(&mut self, ctx:&mut Ctx);
+
+    /// called before a client joins the instance with the `payload` it sent in `ClientMsg::JoinInstance`.
+    /// returning `Err` refuses the join, the reason is sent to the client in `ServerMsg::JoinRejected`.
+    /// not called for clients resuming a held seat or when the instance is already full
+    fn can_join(&mut self, _client_id:Uuid, _client_name:&str, _payload:&[u8]) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// called once when the instance ends, e.g. when it is removed or the master shuts down.
+    /// messages pushed to `ctx` are sent to the clients before they are returned to the lobby,
+    /// on shutdown before they are disconnected
+    fn shutdown(&mut self, _ctx:&mut Ctx) {
+    }
+
+    /// returns the state of the server, which is stored periodically if `master::Config::snapshot_dir` is set.
+    /// `None` means the server does not support snapshots
+    fn snapshot(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// restores the state returned by `snapshot`, called after `init` when the master
+    /// recreates the instance on startup. clients are not seated in the restored instance
+    /// and have to join it again
+    fn restore(&mut self, _snapshot:&[u8]) -> Result<(), String> {
+        Err("restore is not supported".into())
+    }
 }
 
 pub type GameServerConstructorFn = Box<dyn Fn() -> Box<dyn Server> + Send + Sync>;
 
+/// constructs a server from the settings of an instance, returning a reason if the settings are invalid
+pub type SettingsConstructorFn = Box<dyn Fn(&[u8]) -> Result<Box<dyn Server>, String> + Send + Sync>;
+
 #[derive(Clone)]
 pub struct Constructor {
-    arc:Arc<GameServerConstructorFn>
+    arc:Arc<SettingsConstructorFn>
 }
 
 
 impl Constructor {
+    /// constructs servers using `f`, ignoring the settings of the instance
     pub fn new_constructor(f:GameServerConstructorFn) -> Self {
+        Self::new_with_settings(Box::new(move |_| Ok(f())))
+    }
+
+    /// constructs servers using `f`, which validates the settings of the instance
+    pub fn new_with_settings(f:SettingsConstructorFn) -> Self {
         Self {
-            arc:Arc::new(Box::new(f))
+            arc:Arc::new(f)
         }
     }
 
+    /// constructs `T` using `Default`, ignoring the settings of the instance
     pub fn new<T:Server + Default>() -> Self {
+        Self::new_with_settings(Box::new(|_| {
+            Ok(Box::new(T::default()))
+        }))
+    }
 
-        let f:fn()->Box<dyn Server> = || {
-            return Box::new(T::default());
-        };
+    /// constructs a `TypedServer`, decoding and encoding its custom messages using `Bincoded`
+    pub fn new_typed<T:TypedServer + Default>() -> Self {
+        Self::new_with_settings(Box::new(|_| {
+            Ok(Box::new(Typed::new(T::default())))
+        }))
+    }
 
-        let boxed = Box::new(f);
-        Self {
-            arc:Arc::new(boxed)
-        }
+    /// constructs servers using `f` with the settings of the instance decoded as `S`.
+    /// settings which cannot be decoded are rejected
+    pub fn with_settings<S:Bincoded, T:Server>(f:impl Fn(S) -> Result<T, String> + Send + Sync + 'static) -> Self {
+        Self::new_with_settings(Box::new(move |settings| {
+            let settings = S::from_bincode(settings).ok_or("invalid settings")?;
+            let server = f(settings)?;
+            Ok(Box::new(server))
+        }))
     }
 
-    pub fn construct(&self) -> Box<dyn Server> {
+    /// constructs a server with the given `settings`
+    pub fn construct(&self, settings:&[u8]) -> Result<Box<dyn Server>, String> {
         let f = self.arc.as_ref();
-        f()
+        f(settings)
     }
 }
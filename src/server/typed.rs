@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+use crate::bincoded::Bincoded;
+use super::{Config, Ctx, InMsg, OutMsg, Server};
+
+/// a server exchanging typed messages with its clients instead of raw bytes
+///
+/// `ClientInput` is what clients send using `ClientMsg::CustomMsg` and `ServerOutput` is
+/// what they receive in `ServerMsg::Custom`. custom messages which cannot be decoded
+/// are passed to `tick` as `InMsg::InvalidMsg`.
+/// construct using `Constructor::new_typed`
+pub trait TypedServer : Send + Sync + 'static {
+    type ClientInput : Bincoded + Send + Sync;
+    type ServerOutput : Bincoded + Send + Sync;
+
+    fn init(&mut self) -> Config;
+    fn tick(&mut self, ctx:&mut Ctx<Self::ClientInput, Self::ServerOutput>);
+
+    /// see `Server::can_join`
+    fn can_join(&mut self, _client_id:Uuid, _client_name:&str, _payload:&[u8]) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// see `Server::shutdown`
+    fn shutdown(&mut self, _ctx:&mut Ctx<Self::ClientInput, Self::ServerOutput>) {
+    }
+
+    /// see `Server::snapshot`
+    fn snapshot(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// see `Server::restore`
+    fn restore(&mut self, _snapshot:&[u8]) -> Result<(), String> {
+        Err("restore is not supported".into())
+    }
+}
+
+/// runs a `TypedServer` as a `Server`
+pub struct Typed<T:TypedServer> {
+    server:T
+}
+
+impl<T:TypedServer> Typed<T> {
+    pub fn new(server:T) -> Self {
+        Self {
+            server
+        }
+    }
+
+    /// returns the wrapped server
+    pub fn inner(&mut self) -> &mut T {
+        &mut self.server
+    }
+
+    /// decodes the messages of `ctx` into a typed context
+    fn decode(ctx:&mut Ctx) -> Ctx<T::ClientInput, T::ServerOutput> {
+        let in_messages = ctx.in_messages.drain(..).map(|msg| match msg {
+            InMsg::CustomMsg { client_id, msg } => match bincode::deserialize::<T::ClientInput>(&msg) {
+                Ok(decoded) => InMsg::CustomMsg { client_id, msg:decoded },
+                Err(err) => InMsg::InvalidMsg { client_id, msg, error:err.to_string() }
+            },
+            InMsg::InvalidMsg { client_id, msg, error } => InMsg::InvalidMsg { client_id, msg, error },
+            InMsg::ClientJoined { client_id, client_name } => InMsg::ClientJoined { client_id, client_name },
+            InMsg::ClientLeft { client_id } => InMsg::ClientLeft { client_id },
+            InMsg::ClientDisconnected { client_id } => InMsg::ClientDisconnected { client_id },
+            InMsg::ClientReconnected { client_id } => InMsg::ClientReconnected { client_id }
+        }).collect();
+
+        Ctx {
+            in_messages,
+            out_messages:VecDeque::new(),
+            delta:ctx.delta,
+            time:ctx.time,
+            tick:ctx.tick,
+            tick_period:ctx.tick_period,
+            ended:ctx.ended.take()
+        }
+    }
+
+    /// encodes the messages pushed to `typed` back into `ctx`
+    fn encode(typed:Ctx<T::ClientInput, T::ServerOutput>, ctx:&mut Ctx) {
+        ctx.out_messages.extend(typed.out_messages.into_iter().map(|msg| match msg {
+            OutMsg::CustomToAll { msg } => OutMsg::CustomToAll { msg:msg.to_bincode() },
+            OutMsg::CustomTo { client_id, msg } => OutMsg::CustomTo { client_id, msg:msg.to_bincode() },
+            OutMsg::Kick { client_id, reason, disconnect } => OutMsg::Kick { client_id, reason, disconnect },
+            OutMsg::UnreliableToAll { msg } => OutMsg::UnreliableToAll { msg:msg.to_bincode() },
+            OutMsg::UnreliableTo { client_id, msg } => OutMsg::UnreliableTo { client_id, msg:msg.to_bincode() }
+        }));
+        ctx.tick_period = typed.tick_period;
+        ctx.ended = typed.ended;
+    }
+}
+
+impl<T:TypedServer> Server for Typed<T> {
+    fn init(&mut self) -> Config {
+        self.server.init()
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        let mut typed = Self::decode(ctx);
+        self.server.tick(&mut typed);
+        Self::encode(typed, ctx);
+    }
+
+    fn can_join(&mut self, client_id:Uuid, client_name:&str, payload:&[u8]) -> Result<(), String> {
+        self.server.can_join(client_id, client_name, payload)
+    }
+
+    fn shutdown(&mut self, ctx:&mut Ctx) {
+        let mut typed = Self::decode(ctx);
+        self.server.shutdown(&mut typed);
+        Self::encode(typed, ctx);
+    }
+
+    fn snapshot(&mut self) -> Option<Vec<u8>> {
+        self.server.snapshot()
+    }
+
+    fn restore(&mut self, snapshot:&[u8]) -> Result<(), String> {
+        self.server.restore(snapshot)
+    }
+}
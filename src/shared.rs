@@ -5,6 +5,16 @@ use uuid::Uuid;
 pub struct InstanceInfo {
     pub id:Uuid,
     pub creator:Uuid,
+    /// the kind of game server hosted by the instance
+    pub kind:String,
     pub max_players:u32,
-    pub current_players:u32
+    pub current_players:u32,
+
+    /// the settings the instance was created with, e.g. map and mode,
+    /// in the format understood by the constructor of its kind
+    pub settings:Vec<u8>,
+
+    /// true if the server of the instance panicked and was not restarted, ending the instance.
+    /// a crashed instance stays listed until it is removed, but cannot be joined
+    pub crashed:bool
 }
\ No newline at end of file
@@ -0,0 +1,209 @@
+use std::{collections::{HashMap, VecDeque}, time::Duration};
+
+use uuid::Uuid;
+
+use crate::server::{Config, Constructor, Ctx, InMsg, OutMsg, Server, clamp_tick_period};
+
+/// drives a `Server` without a `Master` or sockets, for unit testing games
+///
+/// messages are injected with `push` or the helpers like `join` and `send`, and are
+/// passed to the server on the next `tick`. time only advances when ticking, by a fixed delta,
+/// such that `Ctx::delta` and `Ctx::time` are deterministic.
+/// the `OutMsg` values emitted by the server are collected per client
+pub struct Harness {
+    server:Box<dyn Server>,
+    config:Config,
+    ctx:Ctx,
+
+    /// clients seated in the instance and their names
+    clients:HashMap<Uuid, String>,
+
+    /// messages emitted by the server since the last `take_out`
+    out:Vec<OutMsg>,
+
+    /// messages emitted by the server for each client since the last `take_for`
+    received:HashMap<Uuid, Vec<OutMsg>>
+}
+
+impl Harness {
+    /// takes ownership of the `server` and calls its `init`
+    pub fn new(mut server:Box<dyn Server>) -> Self {
+        let config = server.init();
+        let tick_period = clamp_tick_period(config.tick_period);
+        Self {
+            server,
+            config,
+            ctx:Ctx {
+                in_messages:VecDeque::new(),
+                out_messages:VecDeque::new(),
+                delta:0.0,
+                time:0.0,
+                tick:0,
+                tick_period,
+                ended:None
+            },
+            clients:HashMap::new(),
+            out:Vec::new(),
+            received:HashMap::new()
+        }
+    }
+
+    /// constructs the server using `constructor` with the instance `settings`, see `new`
+    pub fn from_constructor(constructor:&Constructor, settings:&[u8]) -> Result<Self, String> {
+        Ok(Self::new(constructor.construct(settings)?))
+    }
+
+    /// returns the config returned by `Server::init`
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// returns the server, e.g. to inspect its state or call `Server::snapshot`
+    pub fn server(&mut self) -> &mut dyn Server {
+        self.server.as_mut()
+    }
+
+    /// returns the number of ticks run so far
+    pub fn ticks(&self) -> u64 {
+        self.ctx.tick
+    }
+
+    /// returns the time in seconds simulated so far
+    pub fn time(&self) -> f64 {
+        self.ctx.time
+    }
+
+    /// returns the reason given to `Ctx::end`, if the server has ended the instance
+    pub fn ended(&self) -> Option<&str> {
+        self.ctx.ended.as_deref()
+    }
+
+    /// returns the clients seated in the instance
+    pub fn clients(&self) -> Vec<Uuid> {
+        self.clients.keys().copied().collect()
+    }
+
+    /// queues `msg` for the next tick as is, without any bookkeeping
+    pub fn push(&mut self, msg:InMsg) {
+        self.ctx.in_messages.push_back(msg);
+    }
+
+    /// seats a client like the master does, asking `Server::can_join` first
+    /// and queueing `InMsg::ClientJoined` if admitted
+    pub fn join(&mut self, client_id:Uuid, client_name:&str, payload:&[u8]) -> Result<(), String> {
+        if self.clients.contains_key(&client_id) {
+            return Err("already in the instance".into());
+        }
+
+        if self.clients.len() as u32 >= self.config.max_players {
+            return Err("instance is full".into());
+        }
+
+        self.server.can_join(client_id, client_name, payload)?;
+        self.clients.insert(client_id, client_name.into());
+        self.push(InMsg::ClientJoined {
+            client_id,
+            client_name:client_name.into()
+        });
+        Ok(())
+    }
+
+    /// removes a client, queueing `InMsg::ClientLeft`
+    pub fn leave(&mut self, client_id:Uuid) {
+        if self.clients.remove(&client_id).is_some() {
+            self.push(InMsg::ClientLeft {
+                client_id
+            });
+        }
+    }
+
+    /// queues a custom message sent by the client
+    pub fn send(&mut self, client_id:Uuid, msg:Vec<u8>) {
+        self.push(InMsg::CustomMsg {
+            client_id,
+            msg
+        });
+    }
+
+    /// ticks the server once, advancing time by the current tick period, see `Ctx::set_tick_period`
+    pub fn tick(&mut self) {
+        let delta = self.ctx.tick_period.as_secs_f64();
+        self.tick_with(delta);
+    }
+
+    /// returns the current tick period, see `Ctx::set_tick_period`
+    pub fn tick_period(&self) -> Duration {
+        self.ctx.tick_period
+    }
+
+    /// ticks the server once, advancing time by `delta` seconds
+    pub fn tick_with(&mut self, delta:f64) {
+        self.ctx.delta = delta;
+        self.ctx.time += delta;
+        self.server.tick(&mut self.ctx);
+        self.ctx.tick += 1;
+        self.ctx.in_messages.clear();
+        self.route();
+    }
+
+    /// ticks the server `ticks` times, see `tick`
+    pub fn run(&mut self, ticks:usize) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    /// calls `Server::shutdown`, collecting the messages it emits
+    pub fn shutdown(&mut self) {
+        self.server.shutdown(&mut self.ctx);
+        self.route();
+    }
+
+    /// returns all messages emitted by the server since the last call
+    pub fn take_out(&mut self) -> Vec<OutMsg> {
+        std::mem::take(&mut self.out)
+    }
+
+    /// returns the messages emitted by the server which reached `client_id` since the last call.
+    /// `OutMsg::CustomToAll` and `OutMsg::UnreliableToAll` are included for every client seated at the time
+    pub fn take_for(&mut self, client_id:Uuid) -> Vec<OutMsg> {
+        self.received.remove(&client_id).unwrap_or_default()
+    }
+
+    /// returns the payloads of the custom messages which reached `client_id` since the last call
+    pub fn take_custom_for(&mut self, client_id:Uuid) -> Vec<Vec<u8>> {
+        self.take_for(client_id).into_iter().filter_map(|msg| match msg {
+            OutMsg::CustomToAll { msg } | OutMsg::CustomTo { msg, .. } => Some(msg),
+            OutMsg::UnreliableToAll { msg } | OutMsg::UnreliableTo { msg, .. } => Some(msg),
+            _ => None
+        }).collect()
+    }
+
+    /// hands the messages emitted by the server to the clients they target, like the master does
+    fn route(&mut self) {
+        for msg in self.ctx.out_messages.drain(..) {
+            match &msg {
+                OutMsg::CustomToAll { .. } | OutMsg::UnreliableToAll { .. } => {
+                    for client_id in self.clients.keys() {
+                        self.received.entry(*client_id).or_default().push(msg.clone());
+                    }
+                },
+                OutMsg::CustomTo { client_id, .. } | OutMsg::UnreliableTo { client_id, .. } => {
+                    if self.clients.contains_key(client_id) {
+                        self.received.entry(*client_id).or_default().push(msg.clone());
+                    }
+                },
+                OutMsg::Kick { client_id, .. } => {
+                    if self.clients.remove(client_id).is_some() {
+                        self.received.entry(*client_id).or_default().push(msg.clone());
+                        self.ctx.in_messages.push_back(InMsg::ClientLeft {
+                            client_id:*client_id
+                        });
+                    }
+                }
+            }
+
+            self.out.push(msg);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::Constructor, master::{AdminInstance, Master}};
+use tokio::time::Duration;
+use uuid::Uuid;
+
+const LISTEN: &str = "127.0.0.1:8090";
+const TOKEN: &str = "s3cret";
+#[tokio::test]
+pub async fn admin() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<EmptyGame>());
+    master.config_mut().admin_token = Some(TOKEN.into());
+    master.config_mut().reconnect_grace = Some(Duration::from_secs(5));
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let (client_id, resume_token) = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, resume_token, .. } => Some((client_id, resume_token)),
+        _ => None
+    }).await;
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    let (status, _) = http(LISTEN, "GET", "/admin/instances", Some("guess"), "").await;
+    assert_eq!(status, 401);
+
+    // list instances with their players
+    let (status, body) = http(LISTEN, "GET", "/admin/instances", Some(TOKEN), "").await;
+    assert_eq!(status, 200);
+    let instances:Vec<AdminInstance> = serde_json::from_str(&body).unwrap();
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].info.id, instance_id);
+    assert_eq!(instances[0].players[0].client_id, client_id);
+
+    // broadcast and kick
+    let (status, _) = http(LISTEN, "POST", &format!("/admin/instances/{}/broadcast", instance_id), Some(TOKEN), r#"{"message":"restart in 5 minutes"}"#).await;
+    assert_eq!(status, 200);
+    let message = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Announcement { message } => Some(message),
+        _ => None
+    }).await;
+    assert_eq!(message, "restart in 5 minutes");
+
+    let (status, _) = http(LISTEN, "POST", &format!("/admin/clients/{}/kick", client_id), Some(TOKEN), r#"{"reason":"afk"}"#).await;
+    assert_eq!(status, 200);
+    let reason = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Kicked { reason } => Some(reason),
+        _ => None
+    }).await;
+    assert_eq!(reason, "afk");
+
+    // the kicked client cannot resume its session
+    drop(ws);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: Some(resume_token), compression: Vec::new() }).await;
+    let resumed_id = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, .. } => Some(client_id),
+        _ => None
+    }).await;
+    assert_ne!(resumed_id, client_id);
+
+    // create and destroy instances
+    let (status, _) = http(LISTEN, "POST", "/admin/instances", Some(TOKEN), "").await;
+    assert_eq!(status, 201);
+    let (status, _) = http(LISTEN, "DELETE", &format!("/admin/instances/{}", instance_id), Some(TOKEN), "").await;
+    assert_eq!(status, 200);
+    let (_, body) = http(LISTEN, "GET", "/admin/instances", Some(TOKEN), "").await;
+    let instances:Vec<AdminInstance> = serde_json::from_str(&body).unwrap();
+    assert_eq!(instances.len(), 1);
+    assert_ne!(instances[0].info.id, instance_id);
+}
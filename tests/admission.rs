@@ -0,0 +1,60 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx}, master::Master};
+use uuid::Uuid;
+
+/// only admits clients asking for the red team
+#[derive(Default)]
+pub struct RedOnly;
+
+impl Server for RedOnly {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, _ctx:&mut Ctx) {
+    }
+
+    fn can_join(&mut self, _client_id:Uuid, client_name:&str, payload:&[u8]) -> Result<(), String> {
+        match payload {
+            b"red" => Ok(()),
+            _ => Err(format!("{} must join the red team", client_name))
+        }
+    }
+}
+
+const LISTEN: &str = "127.0.0.1:8093";
+#[tokio::test]
+pub async fn admission() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<RedOnly>());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+
+    // refused by the server
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: b"blue".to_vec() }).await;
+    let reason = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => panic!("join was not refused"),
+        ServerMsg::JoinRejected { instance, reason } => {
+            assert_eq!(instance.id, instance_id);
+            Some(reason)
+        },
+        _ => None
+    }).await;
+    assert_eq!(reason, "Tester must join the red team");
+
+    // admitted by the server
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: b"red".to_vec() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        ServerMsg::JoinRejected { reason, .. } => panic!("join rejected: {}", reason),
+        _ => None
+    }).await;
+}
@@ -0,0 +1,120 @@
+mod common;
+use std::sync::Arc;
+
+use common::*;
+use futures_util::future::{BoxFuture, FutureExt};
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx, InMsg, OutMsg}, master::{AuthRequest, Authenticator, Identity, Master, TokenAuthenticator}};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// greets joining clients with the name they were given by the master
+#[derive(Default)]
+pub struct GreetGame;
+
+impl Server for GreetGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::ClientJoined { client_id, client_name } = msg {
+                ctx.push_msg(OutMsg::CustomTo {
+                    client_id,
+                    msg:client_name.into_bytes()
+                });
+            }
+        }
+    }
+}
+
+const TOKENS: &str = "
+# token client_id client_name
+secret 6c3b3f0e-3b52-4a8e-9a5e-2c0c1f5b7f10 Alice Liddell
+";
+
+const LISTEN: &str = "127.0.0.1:8084";
+#[tokio::test]
+pub async fn authentication() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<GreetGame>());
+    master.config_mut().authenticator = Arc::new(TokenAuthenticator::parse(TOKENS).unwrap());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.start();
+
+    // wrong token in Hello is rejected
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Mallory".into(), token: Some("guess".into()), resume_token: None, compression: Vec::new() }).await;
+    match recv(&mut ws).await {
+        ServerMsg::HelloRejected { reason } => assert_eq!(reason, "invalid token"),
+        msg => panic!("unexpected {:?}", msg)
+    }
+
+    // token in the query string is accepted and the claimed identity is replaced
+    let mut ws = connect(&format!("{}/?token=secret", LISTEN)).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Mallory".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let client_id = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, .. } => Some(client_id),
+        _ => None
+    }).await;
+    assert_eq!(client_id.to_string(), "6c3b3f0e-3b52-4a8e-9a5e-2c0c1f5b7f10");
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    let greeting = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(greeting, b"Alice Liddell");
+}
+
+/// accepts every client, but makes clients named "Slow" wait until `release` is notified,
+/// like an identity provider which is slow to answer
+pub struct SlowAuthenticator {
+    release:Arc<Notify>
+}
+
+impl Authenticator for SlowAuthenticator {
+    fn authenticate<'a>(&'a self, request:&'a AuthRequest) -> BoxFuture<'a, Result<Identity, String>> {
+        async move {
+            if request.client_name == "Slow" {
+                self.release.notified().await;
+            }
+
+            Ok(Identity {
+                client_id:Uuid::new_v4(),
+                client_name:request.client_name.clone()
+            })
+        }.boxed()
+    }
+}
+
+const LISTEN_SLOW: &str = "127.0.0.1:8113";
+#[tokio::test]
+pub async fn slow_authentication() {
+    watchdog(5);
+
+    let release = Arc::new(Notify::new());
+    let mut master = Master::new(LISTEN_SLOW, Constructor::new::<GreetGame>());
+    master.config_mut().authenticator = Arc::new(SlowAuthenticator { release:release.clone() });
+    master.start();
+
+    let mut slow = connect(LISTEN_SLOW).await;
+    send(&mut slow, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Slow".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+
+    // other clients are authenticated while the first one waits
+    let mut fast = connect(LISTEN_SLOW).await;
+    send(&mut fast, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Fast".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    recv_until(&mut fast, |msg| match msg {
+        ServerMsg::JoinedLobby { .. } => Some(()),
+        _ => None
+    }).await;
+
+    release.notify_one();
+    recv_until(&mut slow, |msg| match msg {
+        ServerMsg::JoinedLobby { .. } => Some(()),
+        _ => None
+    }).await;
+}
@@ -15,6 +15,7 @@ pub struct TestGame {
 }
 
 impl Server for TestGame {
+    #[allow(clippy::clone_on_copy)]
     fn tick(&mut self, context: &mut Ctx) {
         let messages = context.pop_all();
         for msg in messages.iter() {
@@ -34,14 +35,15 @@ impl Server for TestGame {
                         msg: msg.clone(),
                     });
                 },
+                _ => {}
             }
         }
     }
 
     fn init(&mut self) -> hostess::server::Config {
         Config {
-            tick_rate:20,
-            max_players:1
+            max_players:1,
+            ..Default::default()
         }
     }
 }
@@ -56,6 +58,7 @@ async fn send<T: SinkExt<Message> + Unpin>(t: &mut T, msg: ClientMsg) {
     let _ = t.send(Message::binary(msg.to_bincode())).await;
 }
 
+#[allow(clippy::needless_return)]
 async fn recv<T: Unpin + Stream<Item = Result<Message, U>>, U : std::fmt::Debug>(t: &mut T) -> ServerMsg {
     let res = t.next().await.unwrap().unwrap();
     match res {
@@ -68,6 +71,7 @@ async fn recv<T: Unpin + Stream<Item = Result<Message, U>>, U : std::fmt::Debug>
 
 const LISTEN: &str = "127.0.0.1:8080";
 #[tokio::test]
+#[allow(clippy::let_underscore_future, clippy::bool_assert_comparison)]
 pub async fn basics() {
     // setup watchdog to ensure test exists
     tokio::spawn( async {
@@ -81,7 +85,7 @@ pub async fn basics() {
 
         for _ in 0..10 {
             master.new_instance(Uuid::default())
-                .await;
+                .await.unwrap();
         }
 
         let _ = master.start().await;
@@ -99,6 +103,9 @@ pub async fn basics() {
         ClientMsg::Hello {
             client_id: Uuid::default(),
             client_name: "Tester".into(),
+            token: None,
+            resume_token: None,
+            compression: Vec::new(),
         },
     )
     .await;
@@ -108,7 +115,7 @@ pub async fn basics() {
     loop {
         let msg = recv(&mut ws_stream).await;
         match msg {
-            ServerMsg::JoinedLobby {  } => {
+            ServerMsg::JoinedLobby { .. } => {
                 lobby_joined = true;
             },
             ServerMsg::Instances { instances } => {
@@ -117,7 +124,7 @@ pub async fn basics() {
                     assert_eq!(instances.len(), 10);
                     let first = instances.first().unwrap();
                     joined_instance = Some(first.clone());
-                    send(&mut ws_stream, ClientMsg::JoinInstance { instance_id:first.id}).await;
+                    send(&mut ws_stream, ClientMsg::JoinInstance { instance_id:first.id, payload:Vec::new() }).await;
                 }
             },
             ServerMsg::JoinedInstance { instance } => {
@@ -133,8 +140,10 @@ pub async fn basics() {
                 break;
             },
             ServerMsg::JoinRejected {
-                instance:_
-            } => { }
+                instance:_,
+                reason:_
+            } => { },
+            _ => {}
         }
     }
 }
@@ -0,0 +1,123 @@
+#![allow(dead_code)]
+use std::process::exit;
+use futures_util::{SinkExt, StreamExt};
+use hostess::{bincoded::Bincoded, client::{ClientMsg, ServerMsg, native_client::{NativeClient, Transport}}, server::{Config, Ctx, InMsg, OutMsg, Server}};
+use tokio::{net::TcpStream, time::Duration};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream
+};
+
+pub type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// a server doing nothing, for tests about the master
+#[derive(Default)]
+pub struct EmptyGame;
+
+impl Server for EmptyGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        ctx.pop_all();
+    }
+}
+
+/// sends every custom message to all clients
+#[derive(Default)]
+pub struct Echo;
+
+impl Server for Echo {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { msg, .. } = msg {
+                ctx.push_msg(OutMsg::CustomToAll { msg });
+            }
+        }
+    }
+}
+
+/// exits the test process if the test has not completed within `secs`
+pub fn watchdog(secs:u64) {
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(secs)).await;
+        exit(1);
+    });
+}
+
+/// connects to the master listening on `addr`, retrying until it is up
+pub async fn connect(addr:&str) -> Ws {
+    loop {
+        let req = format!("ws://{}", addr).into_client_request().unwrap();
+        if let Ok((ws, _)) = connect_async(req).await {
+            return ws;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+pub async fn send(ws:&mut Ws, msg:ClientMsg) {
+    let _ = ws.send(Message::binary(msg.to_bincode())).await;
+}
+
+pub async fn recv(ws:&mut Ws) -> ServerMsg {
+    loop {
+        match ws.next().await.unwrap().unwrap() {
+            Message::Binary(b) => return ServerMsg::from_bincode(&b).unwrap(),
+            Message::Close(_) => panic!("connection closed"),
+            _ => {}
+        }
+    }
+}
+
+/// receives messages until `f` returns `Some`
+pub async fn recv_until<T, F:FnMut(ServerMsg) -> Option<T>>(ws:&mut Ws, mut f:F) -> T {
+    loop {
+        if let Some(res) = f(recv(ws).await) {
+            return res;
+        }
+    }
+}
+
+/// waits for a message of `client` for which `f` returns `Some`
+pub async fn next<T, C:Transport>(client:&NativeClient<C>, mut f:impl FnMut(ServerMsg) -> Option<T>) -> T {
+    loop {
+        for msg in client.messages().await.expect("disconnected") {
+            if let Some(res) = f(msg) {
+                return res;
+            }
+        }
+    }
+}
+
+/// performs a plain HTTP/1.1 request, returning the status code and body
+pub async fn http(addr:&str, method:&str, path:&str, token:Option<&str>, body:&str) -> (u16, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut req = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n", method, path, addr, body.len());
+    if let Some(token) = token {
+        req += &format!("Authorization: Bearer {}\r\n", token);
+    }
+    req += "\r\n";
+    req += body;
+    stream.write_all(req.as_bytes()).await.unwrap();
+
+    let mut res = String::new();
+    stream.read_to_string(&mut res).await.unwrap();
+    let status = res.split(' ').nth(1).unwrap().parse().unwrap();
+    let body = res.split("\r\n\r\n").nth(1).unwrap_or_default().to_string();
+    (status, body)
+}
@@ -0,0 +1,139 @@
+mod common;
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use hostess::{bincoded::Bincoded, client::{ClientFraming, ClientMsg, Codec, Compression, Framing, ServerMsg, tcp_client::TcpClient, tungstenite_client::TungsteniteClient}, server::Constructor, master::Master};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+/// receives the next binary frame as sent over the wire
+async fn recv_frame(ws:&mut Ws) -> Vec<u8> {
+    loop {
+        match ws.next().await.unwrap().unwrap() {
+            Message::Binary(b) => return b,
+            Message::Close(_) => panic!("connection closed"),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+pub fn framing() {
+    let lz4 = Codec { compression: Compression::Lz4, threshold: 1024 };
+    let framing = Framing { framed: true, codec: Some(lz4) };
+
+    // large compressible messages are compressed
+    let large = vec![7; 10000];
+    let frame = framing.encode(large.clone());
+    assert!(frame.len() < 1000);
+    assert_eq!(framing.decode(frame), Some(large));
+
+    // small and incompressible messages are sent as is behind the flag byte
+    let small = vec![7; 100];
+    assert_eq!(framing.encode(small.clone()).len(), small.len() + 1);
+    let mut seed = 0x2545f4914f6cdd1du64;
+    let noise:Vec<u8> = (0..4096).map(|_| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed >> 56) as u8
+    }).collect();
+    assert_eq!(framing.encode(noise.clone()).len(), noise.len() + 1);
+    assert_eq!(framing.decode(framing.encode(noise.clone())), Some(noise));
+
+    // unframed messages are untouched
+    assert_eq!(Framing::default().encode(small.clone()), small);
+
+    // malformed frames are rejected
+    assert_eq!(framing.decode(Vec::new()), None);
+    assert_eq!(framing.decode(vec![9, 1, 2]), None);
+    assert_eq!(framing.decode(vec![1, 255, 255, 255, 255, 0]), None);
+}
+
+const LISTEN: &str = "127.0.0.1:8111";
+const LISTEN_TCP: &str = "127.0.0.1:8112";
+#[tokio::test]
+pub async fn compression() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<Echo>());
+    master.config_mut().tcp_addr = Some(LISTEN_TCP.into());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    // a client offering LZ4 gets it with the default threshold
+    let mut ws = connect(LISTEN).await;
+    let mut framing = ClientFraming::default();
+    let hello = ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Compressed".into(), token: None, resume_token: None, compression: vec![Compression::Lz4] };
+    ws.send(Message::binary(framing.encode(&hello))).await.unwrap();
+    let codec = loop {
+        if let Some(ServerMsg::JoinedLobby { compression, .. }) = framing.decode(recv_frame(&mut ws).await) {
+            break compression;
+        }
+    };
+    assert_eq!(codec, Some(Codec { compression: Compression::Lz4, threshold: 1024 }));
+    let join = ClientMsg::JoinInstance { instance_id, payload: Vec::new() };
+    ws.send(Message::binary(framing.encode(&join))).await.unwrap();
+    loop {
+        if let Some(ServerMsg::JoinedInstance { .. }) = framing.decode(recv_frame(&mut ws).await) {
+            break;
+        }
+    }
+
+    // as does a tungstenite client, while a tcp client offering nothing gets raw messages
+    let mut tungstenite = TungsteniteClient::new(&format!("ws://{}", LISTEN)).unwrap();
+    tungstenite.connect().await;
+    tungstenite.send(ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tungstenite".into(), token: None, resume_token: None, compression: vec![Compression::Lz4] }).await;
+    let codec = next(&tungstenite, |msg| match msg {
+        ServerMsg::JoinedLobby { compression, .. } => Some(compression),
+        _ => None
+    }).await;
+    assert!(codec.is_some());
+    tungstenite.send(ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    next(&tungstenite, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    let mut tcp = TcpClient::new(LISTEN_TCP);
+    tcp.connect().await;
+    tcp.send(ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Raw".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let codec = next(&tcp, |msg| match msg {
+        ServerMsg::JoinedLobby { compression, .. } => Some(compression),
+        _ => None
+    }).await;
+    assert_eq!(codec, None);
+    tcp.send(ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    next(&tcp, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    // a large message from the tungstenite client reaches everyone intact
+    let large = vec![42; 10000];
+    assert!(tungstenite.send(ClientMsg::CustomMsg { msg: large.clone() }).await);
+    let received = next(&tcp, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(received, large);
+    let received = next(&tungstenite, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(received, large);
+
+    // and is compressed on the wire to the client which offered compression
+    let (frame, received) = loop {
+        let frame = recv_frame(&mut ws).await;
+        if let Some(ServerMsg::Custom { msg }) = framing.decode(frame.clone()) {
+            break (frame, msg);
+        }
+    };
+    assert_eq!(received, large);
+    assert!(frame.len() < large.len() / 10);
+
+    // small messages are sent as is
+    let small = ClientMsg::CustomMsg { msg: b"beep".to_vec() };
+    let uncompressed = small.to_bincode().len();
+    assert_eq!(framing.encode(&small).len(), uncompressed + 1);
+}
@@ -0,0 +1,130 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx, InMsg}, master::{AdminCrash, AdminInstance, Master, RestartPolicy}};
+use uuid::Uuid;
+
+/// panics when receiving a custom message
+#[derive(Default)]
+pub struct Fragile;
+
+impl Server for Fragile {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { .. } = msg {
+                panic!("division by zero");
+            }
+        }
+    }
+}
+
+/// panics when initialized
+#[derive(Default)]
+pub struct Stillborn;
+
+impl Server for Stillborn {
+    fn init(&mut self) -> Config {
+        panic!("no config");
+    }
+
+    fn tick(&mut self, _ctx:&mut Ctx) {
+    }
+}
+
+/// joins the instance and crashes it, returning the `InstanceEnded` received
+async fn crash(ws:&mut Ws, instance_id:Uuid) -> (hostess::shared::InstanceInfo, String) {
+    send(ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+    send(ws, ClientMsg::CustomMsg { msg: vec![0] }).await;
+    recv_until(ws, |msg| match msg {
+        ServerMsg::InstanceEnded { instance, reason } => Some((instance, reason)),
+        _ => None
+    }).await
+}
+
+const LISTEN: &str = "127.0.0.1:8101";
+const TOKEN: &str = "s3cret";
+#[tokio::test]
+pub async fn crashes() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<Fragile>());
+    master.config_mut().admin_token = Some(TOKEN.into());
+    master.config_mut().restart_policy = RestartPolicy::Limited { max_restarts: 1 };
+    master.config_mut().host_creation = true;
+    master.add_constructor("stillborn", Constructor::new::<Stillborn>());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+
+    // the first crash restarts the instance
+    let (instance, reason) = crash(&mut ws, instance_id).await;
+    assert_eq!(reason, "instance crashed and was restarted");
+    assert!(!instance.crashed);
+    let (_, body) = http(LISTEN, "GET", "/admin/instances", Some(TOKEN), "").await;
+    let instances:Vec<AdminInstance> = serde_json::from_str(&body).unwrap();
+    assert_eq!(instances[0].info.id, instance_id);
+    assert_eq!(instances[0].info.current_players, 0);
+    assert_eq!(instances[0].last_crash.as_deref(), Some("division by zero"));
+
+    // the second crash ends it, but it stays listed as crashed
+    let (instance, reason) = crash(&mut ws, instance_id).await;
+    assert_eq!(reason, "instance crashed");
+    assert!(instance.crashed);
+    send(&mut ws, ClientMsg::RefreshInstances).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances.len(), 1);
+    assert!(instances[0].crashed);
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinRejected { .. } => Some(()),
+        ServerMsg::JoinedInstance { .. } => panic!("joined a crashed instance"),
+        _ => None
+    }).await;
+
+    let (_, body) = http(LISTEN, "GET", "/admin/crashes", Some(TOKEN), "").await;
+    let crashes:Vec<AdminCrash> = serde_json::from_str(&body).unwrap();
+    assert_eq!(crashes.len(), 2);
+    assert!(crashes[0].restarted);
+    assert!(!crashes[1].restarted);
+    assert_eq!(crashes[1].message, "division by zero");
+
+    // until it is removed
+    let (_, body) = http(LISTEN, "GET", "/admin/instances", Some(TOKEN), "").await;
+    let instances:Vec<AdminInstance> = serde_json::from_str(&body).unwrap();
+    assert!(instances[0].info.crashed);
+    let (status, _) = http(LISTEN, "DELETE", &format!("/admin/instances/{}", instance_id), Some(TOKEN), "").await;
+    assert_eq!(status, 200);
+    let (_, body) = http(LISTEN, "GET", "/admin/instances", Some(TOKEN), "").await;
+    let instances:Vec<AdminInstance> = serde_json::from_str(&body).unwrap();
+    assert!(instances.is_empty());
+
+    // a server panicking on creation is refused, without taking down the connection
+    send(&mut ws, ClientMsg::CreateInstance { kind: Some("stillborn".into()), settings: Vec::new() }).await;
+    let reason = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::CreateInstanceRejected { reason } => Some(reason),
+        ServerMsg::InstanceCreated { .. } => panic!("created a panicking instance"),
+        _ => None
+    }).await;
+    assert_eq!(reason, "no config");
+    send(&mut ws, ClientMsg::RefreshInstances).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert!(instances.is_empty());
+}
@@ -0,0 +1,176 @@
+mod common;
+use common::*;
+use hostess::{client::{Bincoded, ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx}, master::{DEFAULT_KIND, Master}};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const LISTEN: &str = "127.0.0.1:8081";
+#[tokio::test]
+pub async fn create_instance() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<EmptyGame>());
+    master.config_mut().host_creation = true;
+    master.config_mut().max_instances_per_creator = 1;
+    master.start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Creator".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let client_id = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, .. } => Some(client_id),
+        _ => None
+    }).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances.len(), 0);
+
+    // create an instance and get auto joined
+    send(&mut ws, ClientMsg::CreateInstance { kind: None, settings: Vec::new() }).await;
+    let created = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::InstanceCreated { instance } => Some(instance),
+        _ => None
+    }).await;
+    assert_eq!(created.creator, client_id);
+    assert_eq!(created.max_players, 4);
+    let joined = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { instance } => Some(instance),
+        _ => None
+    }).await;
+    assert_eq!(joined.id, created.id);
+
+    // back in the lobby, a second instance is refused
+    send(&mut ws, ClientMsg::LeaveInstance {}).await;
+    send(&mut ws, ClientMsg::CreateInstance { kind: None, settings: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::CreateInstanceRejected { reason:_ } => Some(()),
+        ServerMsg::InstanceCreated { .. } => panic!("limit not honored"),
+        _ => None
+    }).await;
+}
+
+#[derive(Default)]
+pub struct CoopGame;
+
+impl Server for CoopGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:2,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        ctx.pop_all();
+    }
+}
+
+const LISTEN_KINDS: &str = "127.0.0.1:8083";
+#[tokio::test]
+pub async fn create_instance_of_kind() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN_KINDS, Constructor::new::<EmptyGame>());
+    master.add_constructor("coop", Constructor::new::<CoopGame>());
+    master.config_mut().host_creation = true;
+    master.config_mut().max_instances_per_creator = 0;
+    assert_eq!(master.new_instance_of("deathmatch", Uuid::default()).await, Err("unknown kind 'deathmatch'".into()));
+    master.new_instance_of("coop", Uuid::default()).await.unwrap();
+    master.start();
+
+    let mut ws = connect(LISTEN_KINDS).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Creator".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].kind, "coop");
+    assert_eq!(instances[0].max_players, 2);
+
+    send(&mut ws, ClientMsg::CreateInstance { kind: Some("deathmatch".into()), settings: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::CreateInstanceRejected { .. } => Some(()),
+        _ => None
+    }).await;
+
+    send(&mut ws, ClientMsg::CreateInstance { kind: Some("coop".into()), settings: Vec::new() }).await;
+    let created = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::InstanceCreated { instance } => Some(instance),
+        _ => None
+    }).await;
+    assert_eq!(created.kind, "coop");
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Settings {
+    map:String,
+    max_players:u32
+}
+
+impl Bincoded for Settings {
+}
+
+/// a game configured by the settings of its instance
+pub struct ArenaGame {
+    max_players:u32
+}
+
+impl ArenaGame {
+    pub fn new(settings:Settings) -> Result<Self, String> {
+        if settings.map != "arena" {
+            return Err(format!("unknown map '{}'", settings.map));
+        }
+
+        Ok(Self {
+            max_players:settings.max_players
+        })
+    }
+}
+
+impl Server for ArenaGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:self.max_players,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        ctx.pop_all();
+    }
+}
+
+const LISTEN_SETTINGS: &str = "127.0.0.1:8098";
+#[tokio::test]
+pub async fn create_instance_with_settings() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN_SETTINGS, Constructor::with_settings(ArenaGame::new));
+    master.config_mut().host_creation = true;
+    assert_eq!(master.new_instance_of(DEFAULT_KIND, Uuid::default()).await, Err("invalid settings".into()));
+    master.start();
+
+    let mut ws = connect(LISTEN_SETTINGS).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Creator".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+
+    // rejected by the constructor
+    let settings = Settings { map: "maze".into(), max_players: 8 }.to_bincode();
+    send(&mut ws, ClientMsg::CreateInstance { kind: None, settings }).await;
+    let reason = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::CreateInstanceRejected { reason } => Some(reason),
+        _ => None
+    }).await;
+    assert_eq!(reason, "unknown map 'maze'");
+
+    // accepted and echoed in the info
+    let settings = Settings { map: "arena".into(), max_players: 8 }.to_bincode();
+    send(&mut ws, ClientMsg::CreateInstance { kind: None, settings: settings.clone() }).await;
+    let created = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::InstanceCreated { instance } => Some(instance),
+        _ => None
+    }).await;
+    assert_eq!(created.settings, settings);
+    assert_eq!(created.max_players, 8);
+}
@@ -0,0 +1,82 @@
+mod common;
+use std::{collections::HashSet, sync::{Mutex, atomic::{AtomicBool, Ordering}}};
+
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx}, master::{Execution, Master, ThreadPool}};
+use tokio::time::Duration;
+use uuid::Uuid;
+
+static THREADS:Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// set once the instances may stop blocking their threads
+static RELEASED:AtomicBool = AtomicBool::new(false);
+
+/// blocks its thread until released, recording the name of the thread
+#[derive(Default)]
+pub struct Heavy;
+
+impl Server for Heavy {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        ctx.pop_all();
+        let name = std::thread::current().name().unwrap_or_default().to_string();
+        THREADS.lock().unwrap().get_or_insert_with(HashSet::new).insert(name);
+        while !RELEASED.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+fn threads() -> HashSet<String> {
+    THREADS.lock().unwrap().clone().unwrap_or_default()
+}
+
+const LISTEN: &str = "127.0.0.1:8102";
+#[tokio::test]
+pub async fn execution() {
+    watchdog(5);
+
+    // heavy instances on their own threads do not stall the lobby
+    let mut master = Master::new(LISTEN, Constructor::new::<Heavy>());
+    master.config_mut().execution = Execution::Thread;
+    let first = master.new_instance(Uuid::default()).await.unwrap();
+    let second = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { .. } => Some(()),
+        _ => None
+    }).await;
+    let expected:HashSet<String> = [first, second].iter().map(|id| format!("instance-{}", id)).collect();
+    while threads() != expected {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // both instances are blocked, yet the lobby answers
+    for _ in 0..5 {
+        send(&mut ws, ClientMsg::RefreshInstances).await;
+        recv_until(&mut ws, |msg| match msg {
+            ServerMsg::Instances { .. } => Some(()),
+            _ => None
+        }).await;
+    }
+    RELEASED.store(true, Ordering::SeqCst);
+
+    // instances share the threads of a pool
+    master.config_mut().execution = Execution::Pool(ThreadPool::new(1));
+    master.new_instance(Uuid::default()).await.unwrap();
+    master.new_instance(Uuid::default()).await.unwrap();
+    while threads().len() == expected.len() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let pooled:Vec<String> = threads().difference(&expected).cloned().collect();
+    assert_eq!(pooled, vec!["hostess-pool-0".to_string()]);
+}
@@ -0,0 +1,97 @@
+mod common;
+use std::path::PathBuf;
+
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, master::{Http, Master}, server::Constructor};
+use tokio::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::client::IntoClientRequest};
+use uuid::Uuid;
+use warp::Filter;
+
+/// connects to `url` sending `origin`, returning `None` if the upgrade is refused
+async fn connect_url(url:&str, origin:Option<&str>) -> Option<Ws> {
+    let mut req = url.into_client_request().unwrap();
+    if let Some(origin) = origin {
+        req.headers_mut().insert("origin", origin.parse().unwrap());
+    }
+
+    let (ws, _) = connect_async(req).await.ok()?;
+    Some(ws)
+}
+
+/// connects to `url`, retrying until the master is up, and waits for the lobby
+async fn join_lobby(url:&str) -> Ws {
+    loop {
+        if let Some(mut ws) = connect_url(url, None).await {
+            send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+            recv_until(&mut ws, |msg| match msg {
+                ServerMsg::JoinedLobby { .. } => Some(()),
+                _ => None
+            }).await;
+            return ws;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// creates a single page app in a new temporary directory
+fn app() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hostess-http-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.html"), "<h1>game</h1>").unwrap();
+    std::fs::write(dir.join("app.js"), "start()").unwrap();
+    dir
+}
+
+const LISTEN: &str = "127.0.0.1:8104";
+#[tokio::test]
+pub async fn routing() {
+    watchdog(5);
+
+    let dir = app();
+    let mut master = Master::new(LISTEN, Constructor::new::<EmptyGame>());
+    master.config_mut().http = Http::new()
+        .ws_path("/ws")
+        .spa(&dir)
+        .allow_origin("https://game.example");
+    master.clone().start();
+
+    // upgrades only on the WebSocket path
+    join_lobby(&format!("ws://{}/ws", LISTEN)).await;
+    assert!(connect_url(&format!("ws://{}/other", LISTEN), None).await.is_none());
+
+    // and only from allowed origins
+    assert!(connect_url(&format!("ws://{}/ws", LISTEN), Some("https://evil.example")).await.is_none());
+    assert!(connect_url(&format!("ws://{}/ws", LISTEN), Some("https://game.example")).await.is_some());
+
+    // static files with the index as fallback
+    assert_eq!(http(LISTEN, "GET", "/app.js", None, "").await, (200, "start()".into()));
+    assert_eq!(http(LISTEN, "GET", "/lobby/settings", None, "").await, (200, "<h1>game</h1>".into()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+const LISTEN_EMBEDDED: &str = "127.0.0.1:8105";
+#[tokio::test]
+pub async fn embedded() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN_EMBEDDED, Constructor::new::<EmptyGame>());
+    master.config_mut().http = Http::new()
+        .ws_path("/ws")
+        .no_static_files();
+    master.config_mut().metrics = true;
+    let health = warp::path!("health").map(|| "ok");
+    let routes = health.or(warp::path("hostess").and(master.routes()));
+    master.clone().start_embedded();
+    tokio::spawn(warp::serve(routes).run(LISTEN_EMBEDDED.parse::<std::net::SocketAddr>().unwrap()));
+
+    // hostess is mounted next to the routes of the app
+    join_lobby(&format!("ws://{}/hostess/ws", LISTEN_EMBEDDED)).await;
+    assert_eq!(http(LISTEN_EMBEDDED, "GET", "/health", None, "").await, (200, "ok".into()));
+    let (status, _) = http(LISTEN_EMBEDDED, "GET", "/hostess/metrics", None, "").await;
+    assert_eq!(status, 200);
+    let (status, _) = http(LISTEN_EMBEDDED, "GET", "/hostess/index.html", None, "").await;
+    assert_eq!(status, 404);
+}
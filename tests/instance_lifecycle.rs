@@ -0,0 +1,333 @@
+mod common;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use common::*;
+use futures_util::StreamExt;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx, InMsg, OutMsg, Idle, Timestep}, master::Master};
+use tokio_tungstenite::tungstenite::Message;
+use tokio::time::Duration;
+use uuid::Uuid;
+
+/// ends the match as soon as a custom message is received
+#[derive(Default)]
+pub struct ShortGame;
+
+impl Server for ShortGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { .. } = msg {
+                ctx.end("match finished");
+            }
+        }
+    }
+}
+
+const LISTEN: &str = "127.0.0.1:8082";
+#[tokio::test]
+pub async fn instance_lifecycle() {
+    watchdog(8);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<ShortGame>());
+    master.config_mut().empty_instance_timeout = Some(Duration::from_secs(2));
+    for _ in 0..3 {
+        master.new_instance(Uuid::default()).await.unwrap();
+    }
+    master.clone().start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances.len(), 3);
+
+    // the server ends the match
+    send(&mut ws, ClientMsg::JoinInstance { instance_id: instances[0].id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+    send(&mut ws, ClientMsg::CustomMsg { msg: vec![0] }).await;
+    let reason = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::InstanceEnded { instance, reason } => {
+            assert_eq!(instance.id, instances[0].id);
+            Some(reason)
+        },
+        _ => None
+    }).await;
+    assert_eq!(reason, "match finished");
+
+    // the master removes the instance
+    send(&mut ws, ClientMsg::JoinInstance { instance_id: instances[1].id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+    assert!(master.remove_instance(instances[1].id).await);
+    let ended = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::InstanceEnded { instance, .. } => Some(instance),
+        _ => None
+    }).await;
+    assert_eq!(ended.id, instances[1].id);
+
+    // the last instance is reaped after being empty
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    send(&mut ws, ClientMsg::RefreshInstances).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances.len(), 0);
+}
+
+/// kicks clients sending a custom message, disconnecting them if the message is non-empty
+#[derive(Default)]
+pub struct Bouncer {
+    players:u32
+}
+
+impl Server for Bouncer {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            match msg {
+                InMsg::ClientJoined { .. } => self.players += 1,
+                InMsg::ClientLeft { .. } => {
+                    self.players -= 1;
+                    ctx.push_msg(OutMsg::CustomToAll { msg: vec![self.players as u8] });
+                },
+                InMsg::CustomMsg { client_id, msg } => {
+                    ctx.push_msg(OutMsg::Kick { client_id, reason: "no talking".into(), disconnect: !msg.is_empty() });
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+const LISTEN_KICK: &str = "127.0.0.1:8094";
+#[tokio::test]
+pub async fn kick() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN_KICK, Constructor::new::<Bouncer>());
+    master.config_mut().reconnect_grace = Some(Duration::from_secs(5));
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    let mut witness = connect(LISTEN_KICK).await;
+    send(&mut witness, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Witness".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    send(&mut witness, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut witness, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    let mut ws = connect(LISTEN_KICK).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let (client_id, resume_token) = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, resume_token, .. } => Some((client_id, resume_token)),
+        _ => None
+    }).await;
+
+    // kicked back to the lobby
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+    send(&mut ws, ClientMsg::CustomMsg { msg: Vec::new() }).await;
+    let reason = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Kicked { reason } => Some(reason),
+        _ => None
+    }).await;
+    assert_eq!(reason, "no talking");
+    let players = recv_until(&mut witness, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg[0]),
+        _ => None
+    }).await;
+    assert_eq!(players, 1);
+    send(&mut ws, ClientMsg::RefreshInstances).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances[0].current_players, 1);
+
+    // kicked and disconnected
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+    send(&mut ws, ClientMsg::CustomMsg { msg: vec![1] }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Kicked { .. } => Some(()),
+        _ => None
+    }).await;
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            _ => {}
+        }
+    }
+
+    // and cannot resume its session
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut ws = connect(LISTEN_KICK).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: Some(resume_token), compression: Vec::new() }).await;
+    let resumed_id = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, .. } => Some(client_id),
+        _ => None
+    }).await;
+    assert_ne!(resumed_id, client_id);
+}
+
+static SLEEPER_TICKS:AtomicU32 = AtomicU32::new(0);
+
+/// only ticks while clients are seated, doubling its tick rate on every custom message
+#[derive(Default)]
+pub struct Sleeper;
+
+impl Server for Sleeper {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            idle:Idle::Sleep,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        SLEEPER_TICKS.fetch_add(1, Ordering::SeqCst);
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { .. } = msg {
+                ctx.set_tick_period(ctx.tick_period() / 2);
+                ctx.push_msg(OutMsg::CustomToAll { msg: vec![ctx.tick_period().as_millis() as u8] });
+            }
+        }
+    }
+}
+
+const LISTEN_IDLE: &str = "127.0.0.1:8099";
+#[tokio::test]
+pub async fn idle() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN_IDLE, Constructor::new::<Sleeper>());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    // time is only paused while no messages are in flight, as it would
+    // otherwise jump ahead while waiting for the sockets
+
+    // empty instances do not tick
+    tokio::time::pause();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(SLEEPER_TICKS.load(Ordering::SeqCst), 0);
+    tokio::time::resume();
+
+    let mut ws = connect(LISTEN_IDLE).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    // the tick rate can be changed by the server
+    send(&mut ws, ClientMsg::CustomMsg { msg: vec![0] }).await;
+    let period = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg[0]),
+        _ => None
+    }).await;
+    assert_eq!(period, 25);
+    tokio::time::pause();
+
+    // start counting right after a tick, such that no tick is due at the end
+    let ticks = SLEEPER_TICKS.load(Ordering::SeqCst);
+    while SLEEPER_TICKS.load(Ordering::SeqCst) == ticks {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let ticks = SLEEPER_TICKS.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(510)).await;
+    assert_eq!(SLEEPER_TICKS.load(Ordering::SeqCst) - ticks, 20);
+    tokio::time::resume();
+
+    // and stop again once empty
+    send(&mut ws, ClientMsg::LeaveInstance {}).await;
+    loop {
+        send(&mut ws, ClientMsg::RefreshInstances).await;
+        let instances = recv_until(&mut ws, |msg| match msg {
+            ServerMsg::Instances { instances } => Some(instances),
+            _ => None
+        }).await;
+        if instances[0].current_players == 0 {
+            break;
+        }
+    }
+    tokio::time::pause();
+
+    // after the tick passing on the leave
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let ticks = SLEEPER_TICKS.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(SLEEPER_TICKS.load(Ordering::SeqCst), ticks);
+}
+
+static DOZER_TICKS:AtomicU32 = AtomicU32::new(0);
+
+/// only ticks while clients are seated, with a fixed timestep
+#[derive(Default)]
+pub struct Dozer;
+
+impl Server for Dozer {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            idle:Idle::Sleep,
+            timestep:Timestep::Fixed { max_catch_up: 10 },
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, _ctx:&mut Ctx) {
+        DOZER_TICKS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+const LISTEN_REAP: &str = "127.0.0.1:8116";
+#[tokio::test]
+pub async fn reap_sleeping() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN_REAP, Constructor::new::<Dozer>());
+    master.config_mut().empty_instance_timeout = Some(Duration::from_millis(300));
+    master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    // a sleeping instance is reaped without ticking
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    let mut ws = connect(LISTEN_REAP).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let instances = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances.len(), 0);
+    assert_eq!(DOZER_TICKS.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,66 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx}, master::Master};
+use tokio::time::Duration;
+use uuid::Uuid;
+
+#[derive(Default)]
+pub struct Quiet;
+
+impl Server for Quiet {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, _ctx:&mut Ctx) {
+    }
+}
+
+const LISTEN: &str = "127.0.0.1:8092";
+#[tokio::test]
+pub async fn lobby_updates() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<Quiet>());
+    master.config_mut().lobby_update_interval = Some(Duration::from_millis(100));
+    master.clone().start();
+
+    let mut watcher = connect(LISTEN).await;
+    send(&mut watcher, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Watcher".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let instances = recv_until(&mut watcher, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances.len(), 0);
+
+    // instances created after joining the lobby are pushed
+    let id = master.new_instance(Uuid::default()).await.unwrap();
+    let added = recv_until(&mut watcher, |msg| match msg {
+        ServerMsg::InstanceAdded { instance } => Some(instance),
+        _ => None
+    }).await;
+    assert_eq!(added.id, id);
+    assert_eq!(added.current_players, 0);
+
+    // players joining are pushed as updates
+    let mut player = connect(LISTEN).await;
+    send(&mut player, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Player".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    send(&mut player, ClientMsg::JoinInstance { instance_id: id, payload: Vec::new() }).await;
+    let updated = recv_until(&mut watcher, |msg| match msg {
+        ServerMsg::InstanceUpdated { instance } => Some(instance),
+        _ => None
+    }).await;
+    assert_eq!(updated.id, id);
+    assert_eq!(updated.current_players, 1);
+
+    // removed instances are pushed
+    assert!(master.remove_instance(id).await);
+    let removed = recv_until(&mut watcher, |msg| match msg {
+        ServerMsg::InstanceRemoved { instance_id } => Some(instance_id),
+        _ => None
+    }).await;
+    assert_eq!(removed, id);
+}
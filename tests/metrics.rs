@@ -0,0 +1,73 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx}, master::Master};
+use uuid::Uuid;
+
+#[derive(Default)]
+pub struct SoloGame;
+
+impl Server for SoloGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:1,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        ctx.pop_all();
+    }
+}
+
+async fn join(instance_id:Uuid) -> (Ws, ServerMsg) {
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    let msg = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } | ServerMsg::JoinRejected { .. } => Some(msg),
+        _ => None
+    }).await;
+    (ws, msg)
+}
+
+const LISTEN: &str = "127.0.0.1:8091";
+#[tokio::test]
+pub async fn metrics() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<SoloGame>());
+    master.config_mut().metrics = true;
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.new_instance(Uuid::default()).await.unwrap();
+    master.start();
+
+    let (_a, msg) = join(instance_id).await;
+    assert!(matches!(msg, ServerMsg::JoinedInstance { .. }));
+    let (_b, msg) = join(instance_id).await;
+    assert!(matches!(msg, ServerMsg::JoinRejected { .. }));
+
+    let (status, body) = http(LISTEN, "GET", "/metrics", None, "").await;
+    assert_eq!(status, 200);
+    let lines:Vec<&str> = body.lines().collect();
+    assert!(lines.contains(&"hostess_connected_clients 2"));
+    assert!(lines.contains(&"hostess_clients_in_lobby 1"));
+    assert!(lines.contains(&"hostess_clients_in_instances 1"));
+    assert!(lines.contains(&"hostess_instances 2"));
+    assert!(lines.contains(&"hostess_join_rejections_total 1"));
+    assert!(lines.contains(&format!("hostess_instance_players{{instance=\"{}\",kind=\"default\"}} 1", instance_id).as_str()));
+    assert!(lines.iter().any(|line| line.starts_with("hostess_bytes_out_total ")));
+}
+
+const LISTEN_DISABLED: &str = "127.0.0.1:8115";
+#[tokio::test]
+pub async fn metrics_disabled_by_default() {
+    watchdog(5);
+
+    Master::new(LISTEN_DISABLED, Constructor::new::<EmptyGame>()).start();
+    let _ws = connect(LISTEN_DISABLED).await;
+
+    // the request falls through to the WebSocket route
+    let (status, body) = http(LISTEN_DISABLED, "GET", "/metrics", None, "").await;
+    assert_ne!(status, 200);
+    assert!(!body.contains("hostess_"));
+}
@@ -0,0 +1,195 @@
+mod common;
+use std::sync::Arc;
+
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx, InMsg, OutMsg}, master::{AllowAll, DuplicateSession, Master}};
+use tokio::time::Duration;
+use uuid::Uuid;
+
+async fn hello(addr:&str, client_id:Uuid) -> (Ws, ServerMsg) {
+    let mut ws = connect(addr).await;
+    send(&mut ws, ClientMsg::Hello { client_id, client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let msg = recv(&mut ws).await;
+    (ws, msg)
+}
+
+const LISTEN_ASSIGN: &str = "127.0.0.1:8085";
+#[tokio::test]
+pub async fn assigned_ids() {
+    watchdog(5);
+    Master::new(LISTEN_ASSIGN, Constructor::new::<EmptyGame>()).start();
+
+    let claimed = Uuid::new_v4();
+    let (_a, a) = hello(LISTEN_ASSIGN, claimed).await;
+    let (_b, b) = hello(LISTEN_ASSIGN, claimed).await;
+    match (a, b) {
+        (ServerMsg::JoinedLobby { client_id:a, .. }, ServerMsg::JoinedLobby { client_id:b, .. }) => {
+            assert_ne!(a, claimed);
+            assert_ne!(a, b);
+        },
+        msgs => panic!("unexpected {:?}", msgs)
+    }
+}
+
+const LISTEN_KICK: &str = "127.0.0.1:8086";
+#[tokio::test]
+pub async fn kick_old_session() {
+    watchdog(5);
+    let mut master = Master::new(LISTEN_KICK, Constructor::new::<EmptyGame>());
+    master.config_mut().authenticator = Arc::new(AllowAll);
+    master.config_mut().duplicate_session = DuplicateSession::KickOld;
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.start();
+
+    let client_id = Uuid::new_v4();
+    let (mut old, _) = hello(LISTEN_KICK, client_id).await;
+    send(&mut old, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut old, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    // the old session is kicked out of the instance, making room for the new one
+    let (mut new, msg) = hello(LISTEN_KICK, client_id).await;
+    assert!(matches!(msg, ServerMsg::JoinedLobby { client_id:id, .. } if id == client_id));
+    recv_until(&mut old, |msg| match msg {
+        ServerMsg::Kicked { .. } => Some(()),
+        _ => None
+    }).await;
+    send(&mut new, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    let instance = recv_until(&mut new, |msg| match msg {
+        ServerMsg::JoinedInstance { instance } => Some(instance),
+        ServerMsg::JoinRejected { .. } => panic!("join rejected"),
+        _ => None
+    }).await;
+    assert_eq!(instance.current_players, 1);
+}
+
+const LISTEN_REJECT: &str = "127.0.0.1:8087";
+#[tokio::test]
+pub async fn reject_new_session() {
+    watchdog(5);
+    let mut master = Master::new(LISTEN_REJECT, Constructor::new::<EmptyGame>());
+    master.config_mut().authenticator = Arc::new(AllowAll);
+    master.config_mut().duplicate_session = DuplicateSession::RejectNew;
+    master.start();
+
+    let client_id = Uuid::new_v4();
+    let (_old, msg) = hello(LISTEN_REJECT, client_id).await;
+    assert!(matches!(msg, ServerMsg::JoinedLobby { .. }));
+    let (_new, msg) = hello(LISTEN_REJECT, client_id).await;
+    assert!(matches!(msg, ServerMsg::HelloRejected { .. }));
+}
+
+/// welcomes reconnected clients back
+#[derive(Default)]
+pub struct ResumeGame;
+
+impl Server for ResumeGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:1,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::ClientReconnected { client_id } = msg {
+                ctx.push_msg(OutMsg::CustomTo {
+                    client_id,
+                    msg:b"welcome back".to_vec()
+                });
+            }
+        }
+    }
+}
+
+const LISTEN_RESUME: &str = "127.0.0.1:8088";
+#[tokio::test]
+pub async fn resume_session() {
+    watchdog(5);
+    let mut master = Master::new(LISTEN_RESUME, Constructor::new::<ResumeGame>());
+    master.config_mut().reconnect_grace = Some(Duration::from_secs(2));
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.start();
+
+    let (mut ws, msg) = hello(LISTEN_RESUME, Uuid::new_v4()).await;
+    let (client_id, resume_token) = match msg {
+        ServerMsg::JoinedLobby { client_id, resume_token, .. } => (client_id, resume_token),
+        msg => panic!("unexpected {:?}", msg)
+    };
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    // drop the connection and come back with the resume token
+    drop(ws);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut ws = connect(LISTEN_RESUME).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: Some(resume_token), compression: Vec::new() }).await;
+    let resumed_id = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, .. } => Some(client_id),
+        _ => None
+    }).await;
+    assert_eq!(resumed_id, client_id);
+
+    // the seat was held, so the instance is still full with only this client
+    let instance = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { instance } => Some(instance),
+        ServerMsg::JoinRejected { .. } => panic!("seat was not held"),
+        _ => None
+    }).await;
+    assert_eq!(instance.current_players, 1);
+    let welcome = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(welcome, b"welcome back");
+}
+
+const LISTEN_HALF_OPEN: &str = "127.0.0.1:8114";
+#[tokio::test]
+pub async fn resume_half_open_session() {
+    watchdog(5);
+    let mut master = Master::new(LISTEN_HALF_OPEN, Constructor::new::<ResumeGame>());
+    master.config_mut().reconnect_grace = Some(Duration::from_secs(2));
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.start();
+
+    let (mut old, msg) = hello(LISTEN_HALF_OPEN, Uuid::new_v4()).await;
+    let (client_id, resume_token) = match msg {
+        ServerMsg::JoinedLobby { client_id, resume_token, .. } => (client_id, resume_token),
+        msg => panic!("unexpected {:?}", msg)
+    };
+    send(&mut old, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut old, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    // come back with the resume token while the old connection still looks alive
+    let mut ws = connect(LISTEN_HALF_OPEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: Some(resume_token), compression: Vec::new() }).await;
+    let resumed_id = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedLobby { client_id, .. } => Some(client_id),
+        _ => None
+    }).await;
+    assert_eq!(resumed_id, client_id);
+
+    // the old session is taken over, handing its seat to the new connection
+    let instance = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { instance } => Some(instance),
+        ServerMsg::JoinRejected { .. } => panic!("seat was not held"),
+        _ => None
+    }).await;
+    assert_eq!(instance.current_players, 1);
+    let welcome = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(welcome, b"welcome back");
+    drop(old);
+}
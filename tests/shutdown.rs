@@ -0,0 +1,73 @@
+mod common;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx, OutMsg}, master::Master};
+use tokio_tungstenite::{connect_async, tungstenite::client::IntoClientRequest};
+use uuid::Uuid;
+
+static SHUTDOWNS:AtomicU32 = AtomicU32::new(0);
+
+#[derive(Default)]
+pub struct CountingGame;
+
+impl Server for CountingGame {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        ctx.pop_all();
+    }
+
+    fn shutdown(&mut self, ctx:&mut Ctx) {
+        SHUTDOWNS.fetch_add(1, Ordering::SeqCst);
+        ctx.push_msg(OutMsg::CustomToAll { msg: b"bye".to_vec() });
+    }
+}
+
+const LISTEN: &str = "127.0.0.1:8089";
+#[tokio::test]
+pub async fn shutdown() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<CountingGame>());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.new_instance(Uuid::default()).await.unwrap();
+    let shutdown = master.shutdown_handle();
+    let running = master.start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    shutdown.shutdown("maintenance");
+
+    // the instance says its goodbyes before the client is disconnected
+    let farewell = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        ServerMsg::ServerShuttingDown { .. } => panic!("disconnected before the farewell"),
+        _ => None
+    }).await;
+    assert_eq!(farewell, b"bye");
+    let reason = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::ServerShuttingDown { reason } => Some(reason),
+        _ => None
+    }).await;
+    assert_eq!(reason, "maintenance");
+
+    // resolves once every instance has ended
+    running.await.unwrap();
+    assert_eq!(SHUTDOWNS.load(Ordering::SeqCst), 2);
+
+    // and no longer accepts connections
+    let req = format!("ws://{}", LISTEN).into_client_request().unwrap();
+    assert!(connect_async(req).await.is_err());
+}
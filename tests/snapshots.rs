@@ -0,0 +1,84 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg}, server::{Config, Server, Constructor, Ctx, InMsg, OutMsg}, master::Master};
+use uuid::Uuid;
+
+/// counts the custom messages received, echoing the count
+#[derive(Default)]
+pub struct Counter {
+    count:u8
+}
+
+impl Server for Counter {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { .. } = msg {
+                self.count += 1;
+                ctx.push_msg(OutMsg::CustomToAll { msg: vec![self.count] });
+            }
+        }
+    }
+
+    fn snapshot(&mut self) -> Option<Vec<u8>> {
+        Some(vec![self.count])
+    }
+
+    fn restore(&mut self, snapshot:&[u8]) -> Result<(), String> {
+        self.count = *snapshot.first().ok_or("empty snapshot")?;
+        Ok(())
+    }
+}
+
+/// joins the instance and sends a custom message, returning the count echoed by the server
+async fn count(addr:&str, instance_id:Uuid) -> u8 {
+    let mut ws = connect(addr).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        ServerMsg::JoinRejected { reason, .. } => panic!("join rejected: {}", reason),
+        _ => None
+    }).await;
+    send(&mut ws, ClientMsg::CustomMsg { msg: vec![0] }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg[0]),
+        _ => None
+    }).await
+}
+
+#[tokio::test]
+pub async fn snapshots() {
+    watchdog(5);
+    let dir = std::env::temp_dir().join(format!("hostess-snapshots-{}", Uuid::new_v4()));
+
+    let mut master = Master::new("127.0.0.1:8096", Constructor::new::<Counter>());
+    master.config_mut().snapshot_dir = Some(dir.clone());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    let shutdown = master.shutdown_handle();
+    let running = master.start();
+    assert_eq!(count("127.0.0.1:8096", instance_id).await, 1);
+    assert_eq!(count("127.0.0.1:8096", instance_id).await, 2);
+
+    // a final snapshot is taken on shutdown
+    shutdown.shutdown("restart");
+    running.await.unwrap();
+
+    // the instance is restored with the same id and state
+    let mut master = Master::new("127.0.0.1:8097", Constructor::new::<Counter>());
+    master.config_mut().snapshot_dir = Some(dir.clone());
+    master.clone().start();
+    assert_eq!(count("127.0.0.1:8097", instance_id).await, 3);
+
+    // removed instances are not restored again
+    assert!(master.remove_instance(instance_id).await);
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
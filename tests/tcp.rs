@@ -0,0 +1,57 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg, tcp_client::TcpClient}, server::Constructor, master::Master};
+use uuid::Uuid;
+
+const LISTEN: &str = "127.0.0.1:8106";
+const LISTEN_TCP: &str = "127.0.0.1:8107";
+#[tokio::test]
+pub async fn tcp() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<Echo>());
+    master.config_mut().tcp_addr = Some(LISTEN_TCP.into());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    let mut ws = connect(LISTEN).await;
+    send(&mut ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Browser".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    send(&mut ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(&mut ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    // a native client joins the same instance over raw TCP
+    let mut client = TcpClient::new(LISTEN_TCP);
+    client.connect().await;
+    client.send(ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Bot".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    let instances = next(&client, |msg| match msg {
+        ServerMsg::Instances { instances } => Some(instances),
+        _ => None
+    }).await;
+    assert_eq!(instances[0].current_players, 1);
+    client.send(ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    next(&client, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+
+    // and talks with the WebSocket client
+    assert!(client.send(ClientMsg::CustomMsg { msg: b"beep".to_vec() }).await);
+    let msg = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(msg, b"beep");
+    send(&mut ws, ClientMsg::CustomMsg { msg: b"hello".to_vec() }).await;
+    let mut msgs = Vec::new();
+    while msgs.len() < 2 {
+        for msg in client.messages().await.expect("disconnected") {
+            if let ServerMsg::Custom { msg } = msg {
+                msgs.push(msg);
+            }
+        }
+    }
+    assert_eq!(msgs, vec![b"beep".to_vec(), b"hello".to_vec()]);
+}
@@ -0,0 +1,106 @@
+use hostess::{server::{Config, Server, Ctx, InMsg, OutMsg, tick_rate, MAX_TICK_PERIOD}, testing::Harness};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// echoes custom messages to their sender, broadcasts the time on every tick
+/// and kicks clients sending an empty message
+#[derive(Default)]
+pub struct Echo;
+
+impl Server for Echo {
+    fn init(&mut self) -> Config {
+        Config {
+            tick_period:tick_rate(10.0),
+            max_players:2,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { client_id, msg } = msg {
+                match msg.is_empty() {
+                    true => ctx.push_msg(OutMsg::Kick { client_id, reason: "empty".into(), disconnect: false }),
+                    false => ctx.push_msg(OutMsg::CustomTo { client_id, msg })
+                }
+            }
+        }
+
+        ctx.push_msg(OutMsg::CustomToAll { msg: vec![(ctx.time * 10.0).round() as u8] });
+    }
+
+    fn can_join(&mut self, _client_id:Uuid, client_name:&str, _payload:&[u8]) -> Result<(), String> {
+        match client_name {
+            "Mallory" => Err("not welcome".into()),
+            _ => Ok(())
+        }
+    }
+}
+
+#[test]
+pub fn harness() {
+    let mut harness = Harness::new(Box::new(Echo));
+    let a = Uuid::new_v4();
+    let b = Uuid::new_v4();
+    assert_eq!(harness.join(a, "Alice", &[]), Ok(()));
+    assert_eq!(harness.join(Uuid::new_v4(), "Mallory", &[]), Err("not welcome".into()));
+    assert_eq!(harness.join(b, "Bob", &[]), Ok(()));
+    assert_eq!(harness.join(Uuid::new_v4(), "Carol", &[]), Err("instance is full".into()));
+
+    // time advances by the tick period
+    harness.run(3);
+    assert!((harness.time() - 0.3).abs() < 1e-9);
+    assert_eq!(harness.take_custom_for(a), vec![vec![1], vec![2], vec![3]]);
+    assert_eq!(harness.take_custom_for(b), vec![vec![1], vec![2], vec![3]]);
+    assert_eq!(harness.take_out().len(), 3);
+
+    // messages are routed to their client only
+    harness.send(a, vec![42]);
+    harness.tick_with(0.5);
+    assert_eq!(harness.take_custom_for(a), vec![vec![42], vec![8]]);
+    assert_eq!(harness.take_custom_for(b), vec![vec![8]]);
+
+    // kicked clients leave
+    harness.send(b, Vec::new());
+    harness.tick();
+    assert!(matches!(harness.take_for(b).as_slice(), [OutMsg::Kick { .. }]));
+    assert_eq!(harness.clients(), vec![a]);
+    harness.tick();
+    assert!(harness.take_for(b).is_empty());
+    assert_eq!(harness.take_custom_for(a).len(), 2);
+}
+
+/// ticks at the rate given by the last custom message
+#[derive(Default)]
+pub struct Throttle;
+
+impl Server for Throttle {
+    fn init(&mut self) -> Config {
+        Config {
+            tick_period:tick_rate(0.0),
+            max_players:1,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { msg, .. } = msg {
+                ctx.set_tick_rate(msg[0] as f64);
+            }
+        }
+    }
+}
+
+#[test]
+pub fn tick_rate_changes() {
+    let mut harness = Harness::new(Box::new(Throttle));
+    assert_eq!(harness.tick_period(), MAX_TICK_PERIOD);
+
+    let client_id = Uuid::new_v4();
+    harness.send(client_id, vec![200]);
+    harness.tick();
+    assert_eq!(harness.tick_period(), Duration::from_millis(5));
+    harness.run(2);
+    assert!((harness.time() - MAX_TICK_PERIOD.as_secs_f64() - 0.01).abs() < 1e-9);
+}
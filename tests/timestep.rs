@@ -0,0 +1,95 @@
+use std::sync::Mutex;
+
+use hostess::{server::{Config, Server, Constructor, Ctx, Idle, Timestep}, master::Master};
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// ticks run, with the tick counter, delta, time and when they ran
+static TICKS:Mutex<Vec<(u64, f64, f64, Instant)>> = Mutex::new(Vec::new());
+
+/// records its ticks
+#[derive(Default)]
+pub struct Simulation;
+
+impl Server for Simulation {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            timestep:Timestep::Fixed { max_catch_up: 3 },
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        TICKS.lock().unwrap().push((ctx.tick, ctx.delta, ctx.time, Instant::now()));
+    }
+}
+
+#[tokio::test]
+pub async fn fixed_timestep() {
+    // time only moves when the test lets it
+    tokio::time::pause();
+    let mut master = Master::new("127.0.0.1:8100", Constructor::new::<Simulation>());
+    master.new_instance(Uuid::default()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(TICKS.lock().unwrap().len(), 1);
+
+    // fall behind by more than `max_catch_up` ticks
+    tokio::time::advance(Duration::from_millis(320)).await;
+    tokio::time::sleep(Duration::from_millis(120)).await;
+
+    let ticks = TICKS.lock().unwrap().clone();
+    assert!(ticks.len() >= 7, "ran {} ticks", ticks.len());
+    for (i, (tick, delta, time, _)) in ticks.iter().enumerate() {
+        assert_eq!(*tick, i as u64);
+        assert_eq!(*delta, 0.05);
+        assert!((time - (i + 1) as f64 * 0.05).abs() < 1e-9);
+    }
+
+    // the missed ticks are caught up back to back, but only 3 of them
+    for (_, _, _, at) in &ticks[2..=4] {
+        assert_eq!(*at, ticks[1].3);
+    }
+    let after = ticks[5].3 - ticks[4].3;
+    assert!(after >= Duration::from_millis(50) && after < Duration::from_millis(100), "next tick after {:?}", after);
+}
+
+static IDLE_TICKS:Mutex<Vec<(f64, Instant)>> = Mutex::new(Vec::new());
+
+/// ticks slowly while empty
+#[derive(Default)]
+pub struct IdleSimulation;
+
+impl Server for IdleSimulation {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            idle:Idle::Period(Duration::from_millis(200)),
+            timestep:Timestep::Fixed { max_catch_up: 3 },
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        IDLE_TICKS.lock().unwrap().push((ctx.delta, Instant::now()));
+    }
+}
+
+#[tokio::test]
+pub async fn fixed_timestep_idle() {
+    tokio::time::pause();
+    let mut master = Master::new("127.0.0.1:8117", Constructor::new::<IdleSimulation>());
+    master.new_instance(Uuid::default()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    // an idle instance wakes up with the idle period, but the timestep stays the tick period
+    let ticks = IDLE_TICKS.lock().unwrap().clone();
+    assert!(ticks.len() >= 4, "ran {} ticks", ticks.len());
+    for (delta, _) in &ticks {
+        assert_eq!(*delta, 0.05);
+    }
+    for pair in ticks[1..].windows(2) {
+        let between = pair[1].1 - pair[0].1;
+        assert!(between >= Duration::from_millis(200) && between < Duration::from_millis(250), "ticked after {:?}", between);
+    }
+}
@@ -0,0 +1,83 @@
+mod common;
+use std::{convert::TryFrom, path::Path, sync::Arc};
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use hostess::{bincoded::Bincoded, client::{ClientMsg, ServerMsg}, master::{Master, Tls}, server::Constructor};
+use tokio::{net::TcpStream, time::Duration};
+use tokio_rustls::{TlsConnector, client::TlsStream, rustls::{ClientConfig, RootCertStore, pki_types::{CertificateDer, ServerName}}};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+const CERTS: &str = "tests/certs";
+
+fn cert(name:&str) -> CertificateDer<'static> {
+    let pem = std::fs::read(Path::new(CERTS).join(format!("{}.pem", name))).unwrap();
+    let cert = rustls_pemfile::certs(&mut pem.as_slice()).next().unwrap().unwrap();
+    cert
+}
+
+/// installs the certificate `name` as the one served by the master
+fn install(dir:&Path, name:&str) {
+    for ext in ["pem", "key"] {
+        std::fs::copy(Path::new(CERTS).join(format!("{}.{}", name, ext)), dir.join(format!("server.{}", ext))).unwrap();
+    }
+}
+
+/// connects to `addr` over TLS trusting both test certificates, retrying until the master is up
+async fn connect_tls(addr:&str) -> TlsStream<TcpStream> {
+    let mut roots = RootCertStore::empty();
+    roots.add(cert("first")).unwrap();
+    roots.add(cert("second")).unwrap();
+    let config = ClientConfig::builder_with_provider(Arc::new(tokio_rustls::rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+    loop {
+        if let Ok(tcp) = TcpStream::connect(addr).await {
+            return connector.connect(ServerName::try_from("localhost").unwrap(), tcp).await.unwrap();
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+const LISTEN: &str = "127.0.0.1:8103";
+#[tokio::test]
+pub async fn tls() {
+    watchdog(5);
+
+    let dir = std::env::temp_dir().join(format!("hostess-tls-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    install(&dir, "first");
+
+    let mut master = Master::new(LISTEN, Constructor::new::<EmptyGame>());
+    let mut tls = Tls::new(dir.join("server.pem"), dir.join("server.key"));
+    tls.reload_interval = Duration::from_millis(100);
+    master.config_mut().tls = Some(tls);
+    master.clone().start();
+
+    // the lobby is reachable over wss
+    let stream = connect_tls(LISTEN).await;
+    assert_eq!(stream.get_ref().1.peer_certificates().unwrap()[0], cert("first"));
+    let (mut ws, _) = tokio_tungstenite::client_async(format!("wss://{}", LISTEN), stream).await.unwrap();
+    let hello = ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() };
+    ws.send(Message::binary(hello.to_bincode())).await.unwrap();
+    loop {
+        if let Message::Binary(bytes) = ws.next().await.unwrap().unwrap() {
+            if let Some(ServerMsg::JoinedLobby { .. }) = ServerMsg::from_bincode(&bytes) {
+                break;
+            }
+        }
+    }
+
+    // new connections use the replaced certificate
+    install(&dir, "second");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let stream = connect_tls(LISTEN).await;
+    assert_eq!(stream.get_ref().1.peer_certificates().unwrap()[0], cert("second"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
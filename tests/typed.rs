@@ -0,0 +1,89 @@
+mod common;
+use common::*;
+use hostess::{client::{ClientMsg, ServerMsg, Bincoded, tungstenite_client::TungsteniteClient}, server::{Config, Constructor, Ctx, InMsg, OutMsg, TypedServer}, master::Master};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+pub struct Input {
+    value:u32
+}
+
+impl Bincoded for Input {
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Output {
+    Doubled(u32),
+    Invalid(Uuid)
+}
+
+impl Bincoded for Output {
+}
+
+#[derive(Default)]
+pub struct Doubler;
+
+impl TypedServer for Doubler {
+    type ClientInput = Input;
+    type ServerOutput = Output;
+
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx<Input, Output>) {
+        while let Some(msg) = ctx.pop_msg() {
+            match msg {
+                InMsg::CustomMsg { client_id, msg } => ctx.push_msg(OutMsg::CustomTo { client_id, msg: Output::Doubled(msg.value * 2) }),
+                InMsg::InvalidMsg { client_id, .. } => ctx.push_msg(OutMsg::CustomTo { client_id, msg: Output::Invalid(client_id) }),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// waits for the next typed message
+async fn next_output(client:&TungsteniteClient) -> Result<Output, String> {
+    loop {
+        if let Some(output) = client.custom_messages::<Output>().await.unwrap().into_iter().next() {
+            return output;
+        }
+    }
+}
+
+const LISTEN: &str = "127.0.0.1:8095";
+#[tokio::test]
+pub async fn typed() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new_typed::<Doubler>());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+    // wait for the master to be up, the client retries only once a second
+    connect(LISTEN).await;
+
+    let mut client = TungsteniteClient::new(&format!("ws://{}", LISTEN)).unwrap();
+    client.connect().await;
+    client.send(ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Tester".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    client.send(ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+
+    // typed messages are decoded on both ends
+    client.send_custom(&Input { value: 21 }).await;
+    assert_eq!(next_output(&client).await, Ok(Output::Doubled(42)));
+
+    // all other messages are left queued
+    let messages = client.messages().await.unwrap();
+    assert!(messages.iter().any(|msg| matches!(msg, ServerMsg::JoinedInstance { .. })));
+    let client_id = messages.iter().find_map(|msg| match msg {
+        ServerMsg::JoinedLobby { client_id, .. } => Some(*client_id),
+        _ => None
+    }).unwrap();
+
+    // messages which cannot be decoded are reported to the server
+    client.send(ClientMsg::CustomMsg { msg: vec![1] }).await;
+    assert_eq!(next_output(&client).await, Ok(Output::Invalid(client_id)));
+}
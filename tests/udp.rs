@@ -0,0 +1,124 @@
+mod common;
+use common::*;
+use hostess::{bincoded::Bincoded, client::{ClientMsg, ServerMsg, UdpMsg, native_client::{NativeClient, Transport}, tcp_client::TcpClient, tungstenite_client::TungsteniteClient}, server::{Config, Server, Constructor, Ctx, InMsg, OutMsg}, master::Master};
+use tokio::{net::UdpSocket, time::Duration};
+use uuid::Uuid;
+
+/// sends every custom message to all clients unreliably
+#[derive(Default)]
+pub struct Positions;
+
+impl Server for Positions {
+    fn init(&mut self) -> Config {
+        Config {
+            max_players:4,
+            ..Default::default()
+        }
+    }
+
+    fn tick(&mut self, ctx:&mut Ctx) {
+        while let Some(msg) = ctx.pop_msg() {
+            if let InMsg::CustomMsg { msg, .. } = msg {
+                ctx.push_msg(OutMsg::UnreliableToAll { msg });
+            }
+        }
+    }
+}
+
+/// joins the lobby and returns the offered UDP port and key
+async fn hello(ws:&mut Ws, name:&str) -> (u16, Uuid) {
+    send(ws, ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: name.into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    recv_until(ws, |msg| match msg {
+        ServerMsg::UdpAvailable { port, key } => Some((port, key)),
+        _ => None
+    }).await
+}
+
+async fn join(ws:&mut Ws, instance_id:Uuid) {
+    send(ws, ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    recv_until(ws, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+}
+
+async fn recv_datagram(socket:&UdpSocket) -> UdpMsg {
+    let mut buf = vec![0; 2048];
+    let len = socket.recv(&mut buf).await.unwrap();
+    UdpMsg::from_bincode(&buf[..len]).unwrap()
+}
+
+/// joins a native client to the instance once it is connected over UDP, and
+/// waits for `msg` sent by `ws` to arrive
+async fn receive_native<T:Transport>(client:&mut NativeClient<T>, ws:&mut Ws, instance_id:Uuid, msg:&[u8]) {
+    client.connect().await;
+    client.send(ClientMsg::Hello { client_id: Uuid::new_v4(), client_name: "Bot".into(), token: None, resume_token: None, compression: Vec::new() }).await;
+    while !client.is_udp_connected().await {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    client.send(ClientMsg::JoinInstance { instance_id, payload: Vec::new() }).await;
+    next(client, |msg| match msg {
+        ServerMsg::JoinedInstance { .. } => Some(()),
+        _ => None
+    }).await;
+    send(ws, ClientMsg::CustomMsg { msg: msg.to_vec() }).await;
+    next(client, |received| match received {
+        ServerMsg::Custom { msg: received } if received == msg => Some(()),
+        _ => None
+    }).await;
+}
+
+const LISTEN: &str = "127.0.0.1:8108";
+const LISTEN_TCP: &str = "127.0.0.1:8109";
+const LISTEN_UDP: &str = "127.0.0.1:8110";
+#[tokio::test]
+pub async fn udp() {
+    watchdog(5);
+
+    let mut master = Master::new(LISTEN, Constructor::new::<Positions>());
+    master.config_mut().tcp_addr = Some(LISTEN_TCP.into());
+    master.config_mut().udp_addr = Some(LISTEN_UDP.into());
+    let instance_id = master.new_instance(Uuid::default()).await.unwrap();
+    master.clone().start();
+
+    // the key received over the WebSocket registers the address of the client
+    let mut ws = connect(LISTEN).await;
+    let (port, key) = hello(&mut ws, "Registered").await;
+    assert_eq!(port, 8110);
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(LISTEN_UDP).await.unwrap();
+    socket.send(&UdpMsg::Hello { key: Uuid::new_v4() }.to_bincode()).await.unwrap();
+    socket.send(&UdpMsg::Hello { key }.to_bincode()).await.unwrap();
+    assert!(matches!(recv_datagram(&socket).await, UdpMsg::Welcome));
+    join(&mut ws, instance_id).await;
+
+    // clients which did not register fall back to the WebSocket
+    let mut fallback = connect(LISTEN).await;
+    hello(&mut fallback, "Fallback").await;
+    join(&mut fallback, instance_id).await;
+
+    send(&mut ws, ClientMsg::CustomMsg { msg: b"x=1".to_vec() }).await;
+    match recv_datagram(&socket).await {
+        UdpMsg::Custom { msg } => assert_eq!(msg, b"x=1"),
+        msg => panic!("unexpected {:?}", msg)
+    }
+    let msg = recv_until(&mut fallback, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(msg, b"x=1");
+
+    // as do messages too large for a datagram
+    send(&mut fallback, ClientMsg::CustomMsg { msg: vec![7; 4000] }).await;
+    let msg = recv_until(&mut ws, |msg| match msg {
+        ServerMsg::Custom { msg } => Some(msg),
+        _ => None
+    }).await;
+    assert_eq!(msg.len(), 4000);
+
+    // the native clients receive over UDP, using either transport
+    let mut client = TcpClient::new(LISTEN_TCP);
+    receive_native(&mut client, &mut ws, instance_id, b"x=2").await;
+    let mut client = TungsteniteClient::new(&format!("ws://{}", LISTEN)).unwrap();
+    receive_native(&mut client, &mut ws, instance_id, b"x=3").await;
+}